@@ -1,5 +1,6 @@
+use criterion::black_box;
 use criterion::{criterion_group, criterion_main, Criterion};
-use probable::bloom_filters::{ClassicalBloomFilter, Filter};
+use probable::bloom_filters::{base_hashes, ClassicalBloomFilter, Filter};
 use rand::distributions::Uniform;
 use rand::prelude::IteratorRandom;
 use rand::{thread_rng, Rng};
@@ -27,5 +28,79 @@ pub fn lookup_values(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, lookup_values);
+/// cost of the two base hashes alone, for tiny and long keys
+pub fn hash_keys(c: &mut Criterion) {
+    let short = 42u32.to_be_bytes();
+    let long = vec![7u8; 1024];
+
+    let mut bgroup = c.benchmark_group("hash-keys");
+    bgroup.bench_function("base-hashes-4-bytes", |b| {
+        b.iter(|| base_hashes(black_box(&short)))
+    });
+    bgroup.bench_function("base-hashes-1024-bytes", |b| {
+        b.iter(|| base_hashes(black_box(&long)))
+    });
+}
+
+/// filter of ~60MB, far bigger than the caches, compare runs with and
+/// without the `prefetch` feature
+pub fn lookup_large_filter(c: &mut Criterion) {
+    let mut bf = ClassicalBloomFilter::new(5 * 10u32.pow(7), 0.01);
+    let mut rng = thread_rng();
+    let distribution = Uniform::new_inclusive(0, 10u64.pow(12));
+    let inserted: Vec<[u8; 8]> = (0..10u32.pow(6))
+        .map(|_| rng.sample(distribution).to_be_bytes())
+        .collect();
+    for value in &inserted {
+        bf.insert(value);
+    }
+
+    let mut bgroup = c.benchmark_group("lookup-large-filter");
+    bgroup.bench_function("lookup-random-values", |b| {
+        b.iter(|| bf.lookup(&rng.sample(distribution).to_be_bytes()))
+    });
+
+    bgroup.bench_function("lookup-inserted-values", |b| {
+        b.iter(|| bf.lookup(&inserted[rng.gen_range(0..inserted.len())]))
+    });
+}
+
+/// 50M keys, sequential inserts against `par_build`
+#[cfg(feature = "rayon")]
+pub fn build_large_filter(c: &mut Criterion) {
+    use rayon::prelude::*;
+
+    let n = 5 * 10u32.pow(7);
+    let mut bgroup = c.benchmark_group("build-large-filter");
+    bgroup.sample_size(10);
+    bgroup.bench_function("sequential", |b| {
+        b.iter(|| {
+            let mut bf = ClassicalBloomFilter::new(n, 0.01);
+            for i in 0..n {
+                bf.insert(&i.to_be_bytes());
+            }
+            bf
+        })
+    });
+    bgroup.bench_function("par-build", |b| {
+        b.iter(|| {
+            ClassicalBloomFilter::par_build(
+                n,
+                0.01,
+                (0..n).into_par_iter().map(|i| i.to_be_bytes()),
+            )
+        })
+    });
+}
+
+#[cfg(not(feature = "rayon"))]
+criterion_group!(benches, lookup_values, hash_keys, lookup_large_filter);
+#[cfg(feature = "rayon")]
+criterion_group!(
+    benches,
+    lookup_values,
+    hash_keys,
+    lookup_large_filter,
+    build_large_filter
+);
 criterion_main!(benches);
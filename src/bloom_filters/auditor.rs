@@ -0,0 +1,80 @@
+use crate::bloom_filters::Filter;
+
+/// Wraps a filter to measure its real false positive rate from a stream
+/// of probe values that are known not to be inserted.
+pub struct FalsePositiveAuditor<F> {
+    filter: F,
+    probes: u64,
+    false_positives: u64,
+}
+
+impl<F: Filter> FalsePositiveAuditor<F> {
+    pub fn new(filter: F) -> Self {
+        Self {
+            filter,
+            probes: 0,
+            false_positives: 0,
+        }
+    }
+
+    /// looks up a value that was never inserted, a hit is a false positive
+    pub fn record(&mut self, known_absent: &[u8]) {
+        self.probes += 1;
+        if self.filter.lookup(known_absent) {
+            self.false_positives += 1;
+        }
+    }
+
+    /// fraction of recorded values that were false positives,
+    /// 0 before anything was recorded
+    pub fn observed_fpr(&self) -> f64 {
+        if self.probes == 0 {
+            return 0.0;
+        }
+        self.false_positives as f64 / self.probes as f64
+    }
+
+    /// number of recorded values
+    pub fn probes(&self) -> u64 {
+        self.probes
+    }
+
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    /// the audited filter, e.g. to keep inserting into it
+    pub fn filter_mut(&mut self) -> &mut F {
+        &mut self.filter
+    }
+
+    pub fn into_inner(self) -> F {
+        self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom_filters::ClassicalBloomFilter;
+
+    #[test]
+    fn observed_fpr_tracks_estimate() {
+        let mut auditor = FalsePositiveAuditor::new(ClassicalBloomFilter::new(10000, 0.05));
+        assert_eq!(auditor.observed_fpr(), 0.0);
+        for i in 0..10000u32 {
+            auditor.filter_mut().insert(&i.to_be_bytes());
+        }
+
+        for i in 10000..110000u32 {
+            auditor.record(&i.to_be_bytes());
+        }
+        assert_eq!(auditor.probes(), 100000);
+        let expected = auditor.filter().current_false_positive_rate();
+        assert!(
+            (auditor.observed_fpr() - expected).abs() < expected * 0.1,
+            "observed {}, expected {expected}",
+            auditor.observed_fpr()
+        );
+    }
+}
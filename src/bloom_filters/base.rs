@@ -1,19 +1,947 @@
+use crate::bloom_filters::FilterError;
+use bit_vec::{BitBlock, BitVec};
+use rand::Rng;
+use std::any::Any;
+use std::collections::HashSet;
 use std::f64::consts::LN_2;
+use std::io::{self, BufRead, BufReader, Read};
+
+/// number of bits m required to hold current_items at target_fpr,
+/// same formula as `Filter::calculate_m` but for u64 item counts
+pub fn bits_for_target(current_items: u64, target_fpr: f64) -> u64 {
+    -(target_fpr.ln() * current_items as f64 / (LN_2.powi(2))).ceil() as u64
+}
+
+/// Estimated memory of an exact set against a Bloom filter for the same
+/// items, see `compare_to_exact`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryComparison {
+    pub exact_bytes: u64,
+    pub filter_bytes: u64,
+    /// exact_bytes / filter_bytes
+    pub ratio: f64,
+}
+
+/// Memory of a `HashSet<Vec<u8>>` of n keys of avg_key_bytes against a
+/// filter of `calculate_m(f, n)` bits. The set is estimated as its keys
+/// plus, per bucket, a Vec header and a control byte, with the buckets
+/// the next power of two above n / (7/8) (hashbrown's max load factor).
+pub fn compare_to_exact(n: u32, avg_key_bytes: usize, f: f64) -> MemoryComparison {
+    let buckets = (n as u64 * 8 / 7 + 1).next_power_of_two();
+    let bucket_bytes = (std::mem::size_of::<Vec<u8>>() + 1) as u64;
+    let exact_bytes = buckets * bucket_bytes + n as u64 * avg_key_bytes as u64;
+    let filter_bytes = bits_for_target(n as u64, f).div_ceil(8);
+    MemoryComparison {
+        exact_bytes,
+        filter_bytes,
+        ratio: exact_bytes as f64 / filter_bytes as f64,
+    }
+}
+
+/// relative excess over the target rate tolerated by `Filter::new_strict`
+pub const STRICT_FPR_MARGIN: f64 = 0.05;
+
+/// false positive rate of m bits and k hashes holding n elements,
+/// (1 - e^(-kn/m))^k, 1 for a filter without bits or hashes
+fn achieved_fpr(m: u64, k: u64, n: u32) -> f64 {
+    if m == 0 || k == 0 {
+        return 1.0;
+    }
+    (1.0 - (-(k as f64) * n as f64 / m as f64).exp()).powi(k as i32)
+}
+
+/// Fewest hash functions for which m bits holding n elements have a false
+/// positive rate <= max_fpr, for trading fpr for fewer probes. The rate
+/// falls with k up to the optimum m/n * ln2, None if even that is over.
+pub fn min_k_for_fpr(m: u64, n: u32, max_fpr: f64) -> Option<u64> {
+    if m == 0 {
+        return None;
+    }
+    let optimal_k = (m as f64 / n.max(1) as f64 * LN_2).ceil().max(1.0) as u64;
+    (1..=optimal_k).find(|&k| achieved_fpr(m, k, n) <= max_fpr)
+}
+
+/// growth factor applied to the item count when sizing a rebuilt filter
+pub const REBUILD_HEADROOM: f64 = 2.0;
+
+fn capacity_with_headroom(items: u64) -> u32 {
+    (items as f64 * REBUILD_HEADROOM).clamp(1.0, u32::MAX as f64) as u32
+}
+
+/// Snapshot of a filter's parameters and fill, see `Filter::stats`
+#[derive(Clone, Debug)]
+pub struct FilterStats {
+    /// number of bits
+    pub m: u64,
+    /// number of hash functions
+    pub k: u64,
+    pub set_bits: u64,
+    pub fill_ratio: f64,
+    pub estimated_count: u64,
+    pub current_fpr: f64,
+}
+
+/// fill ratio above which `Filter::health_check` reports saturation
+pub const SATURATION_FILL: f64 = 0.9;
+/// coefficient of variation of the partition fills above which
+/// `Filter::health_check` reports imbalance
+pub const PARTITION_IMBALANCE_LIMIT: f64 = 0.1;
+
+/// Problem found by `Filter::health_check`
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterWarning {
+    /// more elements are estimated than the filter was sized for
+    OverCapacity { estimated: u64, capacity: u64 },
+    /// current false positive rate is over twice the design rate
+    HighFalsePositiveRate { current: f64, target: f64 },
+    /// fill ratio is above `SATURATION_FILL`, lookups mostly succeed
+    Saturated { fill_ratio: f64 },
+    /// partitions are unevenly filled, see
+    /// `PartitionedBloomFilter::partition_imbalance`
+    ImbalancedPartitions { imbalance: f64 },
+    /// a single hash function, every element sets only one bit
+    SingleHashFunction,
+}
+
+/// the checks of `Filter::health_check` that apply to every filter
+pub(crate) fn health_warnings<F: Filter + ?Sized>(filter: &F) -> Vec<FilterWarning> {
+    let mut warnings = Vec::new();
+    let estimated = filter.estimate_count();
+    let capacity = filter.expected_items();
+    if estimated > capacity {
+        warnings.push(FilterWarning::OverCapacity {
+            estimated,
+            capacity,
+        });
+    }
+    let current = filter.current_false_positive_rate();
+    let target = filter.target_fpr();
+    if current > 2.0 * target {
+        warnings.push(FilterWarning::HighFalsePositiveRate { current, target });
+    }
+    let fill_ratio = filter.fill_ratio();
+    if fill_ratio > SATURATION_FILL {
+        warnings.push(FilterWarning::Saturated { fill_ratio });
+    }
+    if filter.num_hashes() == 1 {
+        warnings.push(FilterWarning::SingleHashFunction);
+    }
+    warnings
+}
+
+pub(crate) fn count_ones<B: BitBlock>(storage: &BitVec<B>) -> u64 {
+    storage
+        .blocks()
+        .map(|block| block.count_ones() as u64)
+        .sum()
+}
 
 pub trait Filter {
-    fn new(n: u32, f: f64) -> Self;
+    fn new(n: u32, f: f64) -> Self
+    where
+        Self: Sized;
     fn insert(&mut self, value: &[u8]);
+
+    /// Like `new`, but errors if the m and k the parameters round to give a
+    /// false positive rate at n elements more than `STRICT_FPR_MARGIN`
+    /// above f. The error suggests the smallest n to size for (found by
+    /// doubling) that meets f. Nothing can be sized for n = 0, which errors
+    /// with the suggestion for a single element.
+    fn new_strict(n: u32, f: f64) -> Result<Self, FilterError>
+    where
+        Self: Sized,
+    {
+        let count = n.max(1);
+        let meets_target = |sized_for: u32| {
+            let m = Self::calculate_m(f, sized_for);
+            achieved_fpr(m, Self::calculate_k(m, sized_for), count) <= f * (1.0 + STRICT_FPR_MARGIN)
+        };
+        if n > 0 && meets_target(n) {
+            return Ok(Self::new(n, f));
+        }
+        let mut suggested_n = count;
+        while !meets_target(suggested_n) && suggested_n < u32::MAX {
+            suggested_n = suggested_n.saturating_mul(2);
+        }
+        Err(FilterError::CannotMeetTarget { suggested_n })
+    }
+
+    /// builds a filter sized for the set at false positive rate f
+    /// and inserts every member
+    fn from_set(set: &HashSet<Vec<u8>>, f: f64) -> Self
+    where
+        Self: Sized,
+    {
+        let n = set.len().clamp(1, u32::MAX as usize) as u32;
+        let mut filter = Self::new(n, f);
+        for value in set {
+            filter.insert(value);
+        }
+        filter
+    }
+
+    /// inserts value and returns the sorted, deduplicated indices of the bits
+    /// it set, e.g. for mirroring bit changes into an external store
+    fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize>;
     fn lookup(&self, value: &[u8]) -> bool;
     fn get_size(&self) -> usize;
+    /// total number of bits m
+    fn num_bits(&self) -> u64;
+    /// number of hash functions k
+    fn num_hashes(&self) -> u64;
+    /// number of bits currently set to 1
+    fn set_bits(&self) -> u64;
+
+    /// fraction of bits set to 1
+    fn fill_ratio(&self) -> f64 {
+        self.set_bits() as f64 / self.num_bits() as f64
+    }
+
+    /// probability that a lookup of a not inserted value returns true,
+    /// given the current fill: fill_ratio^k
+    fn current_false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.num_hashes() as i32)
+    }
+
+    /// expected number of false positives among negative_queries
+    /// lookups of not inserted values
+    fn expected_false_positives(&self, negative_queries: u64) -> f64 {
+        negative_queries as f64 * self.current_false_positive_rate()
+    }
+
+    /// Shannon entropy of the bit array from its fill ratio p,
+    /// m * H(p) bits with H(p) = -p log2 p - (1 - p) log2 (1 - p).
+    /// Maximal (m bits) at half fill, 0 when empty or saturated.
+    fn storage_entropy_bits(&self) -> f64 {
+        let p = self.fill_ratio();
+        if p <= 0.0 || p >= 1.0 {
+            return 0.0;
+        }
+        let h = -p * p.log2() - (1.0 - p) * (1.0 - p).log2();
+        self.num_bits() as f64 * h
+    }
+
+    /// estimated number of distinct inserted values from the fill,
+    /// n = -(m/k) * ln(1 - X/m) where X is the number of set bits,
+    /// saturates to u64::MAX once every bit is set
+    fn estimate_count(&self) -> u64 {
+        let m = self.num_bits() as f64;
+        let set_bits = self.set_bits() as f64;
+        (-(m / self.num_hashes() as f64) * (1.0 - set_bits / m).ln()).round() as u64
+    }
+
+    /// Confidence interval around `estimate_count`, the estimate +- z
+    /// standard deviations, e.g. z = 1.96 for ~95%. The estimator's
+    /// variance is approximately m * (e^t - 1 - t) / k^2 with t = k * n / m.
+    fn estimate_count_ci(&self, z: f64) -> (u64, u64) {
+        let m = self.num_bits() as f64;
+        let k = self.num_hashes() as f64;
+        let estimate = self.estimate_count();
+        if estimate == u64::MAX {
+            return (estimate, estimate);
+        }
+        let t = k * estimate as f64 / m;
+        let std_dev = (m * (t.exp() - 1.0 - t)).sqrt() / k;
+        let lower = (estimate as f64 - z * std_dev).floor().max(0.0) as u64;
+        let upper = (estimate as f64 + z * std_dev).ceil() as u64;
+        (lower, upper)
+    }
+
+    /// inserts value unless it already looks present, returns whether it
+    /// was inserted (i.e. was probably new)
+    fn insert_if_absent(&mut self, value: &[u8]) -> bool {
+        if self.lookup(value) {
+            return false;
+        }
+        self.insert(value);
+        true
+    }
+
+    /// `insert_if_absent` for every value, returns how many were probably
+    /// new. Best effort dedup only: a false positive makes a new value
+    /// count as a duplicate, so this undercounts by about the fpr.
+    fn insert_all_new(&mut self, values: &[&[u8]]) -> u64 {
+        values
+            .iter()
+            .filter(|value| self.insert_if_absent(value))
+            .count() as u64
+    }
+
+    /// inserts every value and returns `estimate_count()` afterwards
+    fn insert_all_estimated(&mut self, values: &[&[u8]]) -> u64 {
+        for value in values {
+            self.insert(value);
+        }
+        self.estimate_count()
+    }
+
+    fn stats(&self) -> FilterStats {
+        FilterStats {
+            m: self.num_bits(),
+            k: self.num_hashes(),
+            set_bits: self.set_bits(),
+            fill_ratio: self.fill_ratio(),
+            estimated_count: self.estimate_count(),
+            current_fpr: self.current_false_positive_rate(),
+        }
+    }
+
+    /// `stats()` as a CSV row, columns as in `stats_csv_header`
+    fn stats_csv_row(&self) -> String {
+        let stats = self.stats();
+        format!(
+            "{},{},{},{},{},{}",
+            stats.m,
+            stats.k,
+            stats.set_bits,
+            stats.fill_ratio,
+            stats.estimated_count,
+            stats.current_fpr
+        )
+    }
+
+    fn stats_csv_header() -> &'static str
+    where
+        Self: Sized,
+    {
+        "m,k,set_bits,fill_ratio,estimate_count,current_fpr"
+    }
+
+    /// inserts value only while `fill_ratio()` is below max_fill,
+    /// otherwise returns `FilterError::Full`
+    fn try_insert(&mut self, value: &[u8], max_fill: f64) -> Result<(), FilterError> {
+        if self.fill_ratio() >= max_fill {
+            return Err(FilterError::Full);
+        }
+        self.insert(value);
+        Ok(())
+    }
+
+    fn should_rebuild(&self, target_fpr: f64) -> bool {
+        self.current_false_positive_rate() > target_fpr
+    }
+
+    /// suggested n for a rebuilt filter: the estimated count
+    /// times `REBUILD_HEADROOM`
+    fn rebuild_recommended_capacity(&self) -> u32 {
+        capacity_with_headroom(self.estimate_count())
+    }
+
+    /// If `current_false_positive_rate()` exceeds `max_fpr`, rebuilds the
+    /// filter from `items_source` sized for `REBUILD_HEADROOM` times the
+    /// number of items at `max_fpr`, returns whether a rebuild happened.
+    ///
+    /// Bits can't be turned back into items, so `items_source` must yield
+    /// the authoritative set of inserted items, anything missing from it
+    /// is lost after the rebuild.
+    fn rebuild_if_needed<I: IntoIterator<Item = Vec<u8>>>(
+        &mut self,
+        items_source: impl FnOnce() -> I,
+        max_fpr: f64,
+    ) -> bool
+    where
+        Self: Sized,
+    {
+        if self.current_false_positive_rate() <= max_fpr {
+            return false;
+        }
+        let items: Vec<Vec<u8>> = items_source().into_iter().collect();
+        *self = Self::new(capacity_with_headroom(items.len() as u64), max_fpr);
+        for item in &items {
+            self.insert(item);
+        }
+        true
+    }
+
+    /// Number of elements m and k were sized for, recovered as m/k * ln2.
+    /// k is rounded up on construction, so this can be a bit below the
+    /// original n.
+    fn expected_items(&self) -> u64 {
+        (self.num_bits() as f64 / self.num_hashes() as f64 * LN_2).round() as u64
+    }
+
+    /// false positive rate at `expected_items` elements, which for the
+    /// optimal k is 2^-k
+    fn target_fpr(&self) -> f64 {
+        0.5f64.powi(self.num_hashes() as i32)
+    }
+
+    /// Rebuilds the filter sized for `expected_items() * factor` elements
+    /// at `target_fpr()` and inserts all items into it. As with
+    /// `rebuild_if_needed`, items must be the full set of inserted items:
+    /// the old bits are dropped, anything missing from items is lost.
+    fn grow_into<I: IntoIterator<Item = Vec<u8>>>(self, factor: f64, items: I) -> Self
+    where
+        Self: Sized,
+    {
+        let n = (self.expected_items() as f64 * factor).clamp(1.0, u32::MAX as f64) as u32;
+        let mut grown = Self::new(n, self.target_fpr());
+        for item in items {
+            grown.insert(&item);
+        }
+        grown
+    }
+
+    /// Flags potential problems: over capacity, high false positive rate,
+    /// saturation or a degenerate k = 1. Empty if none were found.
+    fn health_check(&self) -> Vec<FilterWarning> {
+        health_warnings(self)
+    }
+
+    /// inserts every value using its big-endian encoding
+    fn insert_u64_slice(&mut self, values: &[u64]) {
+        for value in values {
+            self.insert(&value.to_be_bytes());
+        }
+    }
+
+    /// inserts v using its big-endian encoding, e.g. for UUIDs
+    fn insert_u128(&mut self, v: u128) {
+        self.insert(&v.to_be_bytes());
+    }
+
+    /// lookup of a value inserted by `insert_u128`
+    fn lookup_u128(&self, v: u128) -> bool {
+        self.lookup(&v.to_be_bytes())
+    }
+
+    /// inserts every integer of range using its big-endian encoding,
+    /// same as `insert_u64_slice`
+    fn insert_u64_range(&mut self, range: std::ops::Range<u64>) {
+        for value in range {
+            self.insert(&value.to_be_bytes());
+        }
+    }
+
+    /// inserts every value using its big-endian encoding
+    fn insert_u32_slice(&mut self, values: &[u32]) {
+        for value in values {
+            self.insert(&value.to_be_bytes());
+        }
+    }
+
+    /// Inserts a key made of several fields. Every field is prefixed with
+    /// its length (u64, big-endian) so that e.g. ["ab", "c"] and ["a", "bc"]
+    /// are different keys.
+    fn insert_fields(&mut self, fields: &[&[u8]]) {
+        self.insert(&encode_fields(fields));
+    }
+
+    /// lookup of a key inserted by `insert_fields`
+    fn lookup_fields(&self, fields: &[&[u8]]) -> bool {
+        self.lookup(&encode_fields(fields))
+    }
+
+    /// Fraction of `samples` random 16-byte values that look up as present.
+    /// Random values are almost surely not inserted, so this approximates
+    /// the false positive rate.
+    fn membership_rate(&self, rng: &mut impl Rng, samples: u32) -> f64
+    where
+        Self: Sized,
+    {
+        if samples == 0 {
+            return 0.0;
+        }
+        let found = (0..samples)
+            .filter(|_| self.lookup(&rng.gen::<[u8; 16]>()))
+            .count();
+        found as f64 / samples as f64
+    }
+
+    /// Inserts value into namespace, the namespace is length-prefixed in
+    /// front of the value as in `insert_fields`, so the same value in two
+    /// namespaces is two unrelated keys.
+    fn insert_namespaced(&mut self, namespace: &[u8], value: &[u8]) {
+        self.insert_fields(&[namespace, value]);
+    }
+
+    /// lookup of a value inserted by `insert_namespaced`
+    fn lookup_namespaced(&self, namespace: &[u8], value: &[u8]) -> bool {
+        self.lookup_fields(&[namespace, value])
+    }
+
+    /// Test helper: panics naming the first value of `inserted` that
+    /// doesn't look up as present, a Bloom filter must never have one.
+    fn assert_contains_inserted(&self, inserted: &[&[u8]]) {
+        for value in inserted {
+            assert!(self.lookup(value), "stored value {value:?} is not found!");
+        }
+    }
+
+    /// Inserts the raw bytes of field `column` (0-based) of every line of
+    /// reader and returns how many were inserted. Lines without that
+    /// column are skipped and a trailing `\r` is dropped. Quoting isn't
+    /// understood: a quoted field holding the delimiter is split and
+    /// quotes stay part of the value.
+    fn insert_csv_column<R: Read>(
+        &mut self,
+        reader: R,
+        column: usize,
+        delimiter: u8,
+    ) -> io::Result<u64>
+    where
+        Self: Sized,
+    {
+        let mut inserted = 0;
+        for line in BufReader::new(reader).split(b'\n') {
+            let line = line?;
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            if let Some(field) = line.split(|&byte| byte == delimiter).nth(column) {
+                self.insert(field);
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// batch lookup, the i-th bit of the result is the answer for `values[i]`
+    fn lookup_mask(&self, values: &[&[u8]]) -> BitVec {
+        values.iter().map(|value| self.lookup(value)).collect()
+    }
 
     /// m = -(nlε/(ln2)^2) where ε is desired false positive probability,
     /// in our case it is indicated by the letter f
-    fn calculate_m(f: f64, n: u32) -> u64 {
-        -(f.ln() * n as f64 / (LN_2.powi(2))).ceil() as u64
+    fn calculate_m(f: f64, n: u32) -> u64
+    where
+        Self: Sized,
+    {
+        bits_for_target(n as u64, f)
     }
 
     /// k = m/n * ln2
-    fn calculate_k(m: u64, n: u32) -> u64 {
+    fn calculate_k(m: u64, n: u32) -> u64
+    where
+        Self: Sized,
+    {
         ((m / n as u64) as f64 * LN_2).ceil() as u64
     }
 }
+
+fn encode_fields(fields: &[&[u8]]) -> Vec<u8> {
+    let len = fields.iter().map(|field| 8 + field.len()).sum();
+    let mut key = Vec::with_capacity(len);
+    for field in fields {
+        key.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        key.extend_from_slice(field);
+    }
+    key
+}
+
+/// Object safe view of a `Filter`, for keeping filters of different
+/// concrete types behind `&dyn FilterDyn` or `Box<dyn FilterDyn>`
+pub trait FilterDyn: Filter {
+    /// for downcasting to the concrete filter, e.g. to call its
+    /// inherent methods
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Filter + Any> FilterDyn for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// number of filters that report value as present,
+/// e.g. "seen on N days" with one filter per day
+pub fn presence_count(filters: &[&dyn FilterDyn], value: &[u8]) -> usize {
+    filters.iter().filter(|filter| filter.lookup(value)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom_filters::{ClassicalBloomFilter, PartitionedBloomFilter};
+
+    #[test]
+    fn bits_for_target_matches_calculate_m() {
+        for n in [1u32, 10, 1000, 10u32.pow(7), u32::MAX] {
+            for f in [0.5, 0.02, 0.001] {
+                assert_eq!(
+                    bits_for_target(n as u64, f),
+                    ClassicalBloomFilter::calculate_m(f, n)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bits_for_target_beyond_u32() {
+        let items = u32::MAX as u64 * 4;
+        assert!(bits_for_target(items, 0.01) > bits_for_target(u32::MAX as u64, 0.01) * 3);
+    }
+
+    #[test]
+    fn estimate_count_ci_covers_true_count() {
+        let trials = 100;
+        let mut covered = 0;
+        for trial in 0..trials {
+            let mut bf = ClassicalBloomFilter::new(2000, 0.01);
+            for i in 0..1500u32 {
+                bf.insert(&(trial * 10000 + i).to_be_bytes());
+            }
+            let (lower, upper) = bf.estimate_count_ci(1.96);
+            assert!(lower <= bf.estimate_count() && bf.estimate_count() <= upper);
+            if (lower..=upper).contains(&1500) {
+                covered += 1;
+            }
+        }
+        assert!(covered >= 85, "only {covered} of {trials} intervals cover");
+    }
+
+    #[test]
+    fn insert_fields_is_unambiguous() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        bf.insert_fields(&[b"ab", b"c"]);
+
+        assert!(
+            bf.lookup_fields(&[b"ab", b"c"]),
+            "stored value is not found!"
+        );
+        assert!(
+            !bf.lookup_fields(&[b"a", b"bc"]),
+            "not stored value is found!"
+        );
+        assert!(!bf.lookup(b"abc"), "not stored value is found!");
+        assert_ne!(encode_fields(&[b"ab", b"c"]), encode_fields(&[b"a", b"bc"]));
+    }
+
+    #[test]
+    fn membership_rate_check() {
+        let mut bf = ClassicalBloomFilter::new(100000, 0.01);
+        for i in 0..10000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert_eq!(bf.membership_rate(&mut rand::thread_rng(), 0), 0.0);
+
+        let samples = 100000;
+        let rate = bf.membership_rate(&mut rand::thread_rng(), samples);
+        let expected = bf.current_false_positive_rate();
+        // binomial standard deviation of the rate
+        let std_dev = (expected / samples as f64).sqrt();
+        assert!(expected < 1e-4);
+        assert!(
+            rate <= expected + 5.0 * std_dev,
+            "rate {rate}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn grow_into_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        let expected_items = bf.expected_items();
+        assert!(expected_items.abs_diff(1000) < 100);
+        let target = bf.target_fpr();
+        assert!(target <= 0.01);
+
+        let items: Vec<Vec<u8>> = (0..2 * expected_items as u32)
+            .map(|i| i.to_be_bytes().to_vec())
+            .collect();
+        items[..1000].iter().for_each(|item| bf.insert(item));
+        let grown = bf.grow_into(2.0, items.clone());
+
+        assert!(grown.expected_items().abs_diff(2 * expected_items) < 200);
+        let inserted: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        grown.assert_contains_inserted(&inserted);
+        assert!(grown.current_false_positive_rate() <= target * 1.05);
+    }
+
+    #[test]
+    fn stats_csv_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..500u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let header = ClassicalBloomFilter::stats_csv_header();
+        let row = bf.stats_csv_row();
+        let fields: Vec<f64> = row.split(',').map(|field| field.parse().unwrap()).collect();
+
+        assert_eq!(fields.len(), header.split(',').count());
+        assert_eq!(fields[0], bf.num_bits() as f64);
+        assert_eq!(fields[1], bf.num_hashes() as f64);
+        assert_eq!(fields[2], bf.set_bits() as f64);
+        assert_eq!(fields[4], bf.estimate_count() as f64);
+    }
+
+    /// drops inserts of one value, like a filter with a false negative bug
+    struct DroppingFilter {
+        inner: ClassicalBloomFilter,
+        dropped: Vec<u8>,
+    }
+
+    impl Filter for DroppingFilter {
+        fn new(n: u32, f: f64) -> Self {
+            Self {
+                inner: ClassicalBloomFilter::new(n, f),
+                dropped: b"dropped".to_vec(),
+            }
+        }
+
+        fn insert(&mut self, value: &[u8]) {
+            if value != self.dropped {
+                self.inner.insert(value);
+            }
+        }
+
+        fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize> {
+            self.inner.insert_tracked(value)
+        }
+
+        fn lookup(&self, value: &[u8]) -> bool {
+            self.inner.lookup(value)
+        }
+
+        fn get_size(&self) -> usize {
+            self.inner.get_size()
+        }
+
+        fn num_bits(&self) -> u64 {
+            self.inner.num_bits()
+        }
+
+        fn num_hashes(&self) -> u64 {
+            self.inner.num_hashes()
+        }
+
+        fn set_bits(&self) -> u64 {
+            self.inner.set_bits()
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is not found")]
+    fn assert_contains_inserted_catches_false_negative() {
+        let mut bf = DroppingFilter::new(100, 0.01);
+        let inserted: [&[u8]; 3] = [b"kept", b"dropped", b"also kept"];
+        for value in inserted {
+            bf.insert(value);
+        }
+        bf.assert_contains_inserted(&inserted);
+    }
+
+    #[test]
+    fn downcast_boxed_filter() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..500u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let boxed: Box<dyn FilterDyn> = Box::new(bf.clone());
+
+        let concrete = boxed.as_any().downcast_ref::<ClassicalBloomFilter>();
+        assert_eq!(
+            concrete.map(|bf| bf.estimate_count()),
+            Some(bf.estimate_count())
+        );
+        assert_eq!(
+            concrete.map(|bf| bf.memory_bytes()),
+            Some(bf.memory_bytes())
+        );
+        assert!(boxed
+            .as_any()
+            .downcast_ref::<PartitionedBloomFilter>()
+            .is_none());
+    }
+
+    #[test]
+    fn insert_csv_column_check() {
+        let csv = "id;name;city\r\n1;alice;paris\r\n2;bob;berlin\n3\n4;carol;rome\n";
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let inserted = bf.insert_csv_column(csv.as_bytes(), 1, b';').unwrap();
+
+        assert_eq!(inserted, 4);
+        bf.assert_contains_inserted(&[b"name", b"alice", b"bob", b"carol"]);
+        for value in [&b"paris"[..], b"berlin", b"rome", b"1", b"3", b"alice\r"] {
+            assert!(!bf.lookup(value), "not stored value is found!");
+        }
+    }
+
+    #[test]
+    fn u128_keys() {
+        let values = [
+            0u128,
+            u128::MAX,
+            0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8,
+            0x550e_8400_e29b_41d4_a716_4466_5544_0000,
+        ];
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let mut reference = ClassicalBloomFilter::new(100, 0.01);
+        for v in values {
+            bf.insert_u128(v);
+            reference.insert(&v.to_be_bytes());
+        }
+
+        assert_eq!(bf, reference);
+        for v in values {
+            assert!(bf.lookup_u128(v), "stored value is not found!");
+        }
+        assert!(!bf.lookup_u128(42), "not stored value is found!");
+    }
+
+    #[test]
+    fn compare_to_exact_check() {
+        let comparison = compare_to_exact(10u32.pow(6), 16, 0.01);
+        assert_eq!(
+            comparison.filter_bytes,
+            ClassicalBloomFilter::calculate_m(0.01, 10u32.pow(6)).div_ceil(8)
+        );
+        assert_eq!(
+            comparison.ratio,
+            comparison.exact_bytes as f64 / comparison.filter_bytes as f64
+        );
+        assert!(comparison.exact_bytes > 10u64.pow(6) * 16);
+        assert!(comparison.ratio > 10.0);
+    }
+
+    #[test]
+    fn insert_u64_range_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        bf.insert_u64_range(0..1000);
+
+        let mut reference = ClassicalBloomFilter::new(1000, 0.01);
+        reference.insert_u64_slice(&(0..1000).collect::<Vec<u64>>());
+        assert_eq!(bf, reference);
+        for i in 0..1000u64 {
+            assert!(bf.lookup(&i.to_be_bytes()), "stored value is not found!");
+        }
+        assert!(
+            !bf.lookup(&5000u64.to_be_bytes()),
+            "not stored value is found!"
+        );
+    }
+
+    #[test]
+    fn new_strict_check() {
+        let bf = ClassicalBloomFilter::new_strict(10000, 0.01).unwrap();
+        assert_eq!(bf, ClassicalBloomFilter::new(10000, 0.01));
+        assert!(achieved_fpr(bf.num_bits(), bf.num_hashes(), 10000) <= 0.01 * 1.05);
+    }
+
+    #[test]
+    fn new_strict_degenerate() {
+        // m = 1 and k = 1, a single element already sets half of the bits
+        let res = ClassicalBloomFilter::new_strict(1, 0.5);
+        let Err(FilterError::CannotMeetTarget { suggested_n }) = res else {
+            panic!("expected CannotMeetTarget, got {res:?}");
+        };
+        let m = ClassicalBloomFilter::calculate_m(0.5, suggested_n);
+        let k = ClassicalBloomFilter::calculate_k(m, suggested_n);
+        assert!(suggested_n > 1);
+        assert!(achieved_fpr(m, k, 1) <= 0.5 * 1.05);
+
+        // rounds to m = 0
+        let res = ClassicalBloomFilter::new_strict(1, 0.9);
+        assert!(matches!(res, Err(FilterError::CannotMeetTarget { .. })));
+
+        // nothing is sized for no elements, the suggestion is for one
+        let res = ClassicalBloomFilter::new_strict(0, 0.01);
+        assert!(matches!(res, Err(FilterError::CannotMeetTarget { .. })));
+        assert_eq!(
+            ClassicalBloomFilter::new_strict(0, 0.5).err(),
+            ClassicalBloomFilter::new_strict(1, 0.5).err()
+        );
+    }
+
+    #[test]
+    fn min_k_for_fpr_check() {
+        let m = ClassicalBloomFilter::calculate_m(0.01, 1000);
+        for max_fpr in [0.2, 0.05, 0.02, 0.011] {
+            let k = min_k_for_fpr(m, 1000, max_fpr).unwrap();
+            assert!(achieved_fpr(m, k, 1000) <= max_fpr);
+            assert!(k == 1 || achieved_fpr(m, k - 1, 1000) > max_fpr);
+        }
+        assert!(min_k_for_fpr(m, 1000, 0.02).unwrap() < min_k_for_fpr(m, 1000, 0.011).unwrap());
+        assert_eq!(min_k_for_fpr(m, 1000, 0.001), None);
+        assert_eq!(min_k_for_fpr(0, 1000, 0.5), None);
+    }
+
+    #[test]
+    fn namespaces_are_isolated() {
+        let mut bf = ClassicalBloomFilter::new(2000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert_namespaced(b"users", &i.to_be_bytes());
+        }
+
+        for i in 0..1000u32 {
+            assert!(
+                bf.lookup_namespaced(b"users", &i.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+        let cross_hits = (0..1000u32)
+            .filter(|i| bf.lookup_namespaced(b"orders", &i.to_be_bytes()))
+            .count();
+        assert!(cross_hits < 20, "{cross_hits} cross-namespace hits");
+    }
+
+    #[test]
+    fn storage_entropy_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        let m = bf.num_bits() as f64;
+        assert_eq!(bf.storage_entropy_bits(), 0.0);
+
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert!((bf.fill_ratio() - 0.5).abs() < 0.05);
+        assert!(bf.storage_entropy_bits() > 0.99 * m);
+
+        for i in 1000..100000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert!(bf.storage_entropy_bits() < 0.01 * m);
+    }
+
+    #[test]
+    fn insert_all_new_check() {
+        let mut bf = ClassicalBloomFilter::new(2000, 0.01);
+        // 2000 distinct values, each one twice
+        let values: Vec<[u8; 4]> = (0..4000u32).map(|i| (i % 2000).to_be_bytes()).collect();
+        let batch: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+
+        let new = bf.insert_all_new(&batch);
+        assert!(new <= 2000 && new > 1950, "{new} new values");
+        bf.assert_contains_inserted(&batch);
+        assert_eq!(bf.insert_all_new(&batch), 0);
+        assert!(!bf.insert_if_absent(&1u32.to_be_bytes()));
+    }
+
+    #[test]
+    fn health_check_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..900u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert_eq!(bf.health_check(), vec![]);
+
+        for i in 900..10000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let warnings = bf.health_check();
+        assert!(warnings
+            .iter()
+            .any(|w| matches!(w, FilterWarning::OverCapacity { .. })));
+        assert!(warnings
+            .iter()
+            .any(|w| matches!(w, FilterWarning::HighFalsePositiveRate { .. })));
+        assert!(warnings
+            .iter()
+            .any(|w| matches!(w, FilterWarning::Saturated { .. })));
+
+        let bf = ClassicalBloomFilter::new(1000, 0.5);
+        assert_eq!(bf.health_check(), vec![FilterWarning::SingleHashFunction]);
+    }
+
+    #[test]
+    fn presence_count_check() {
+        let mut day1 = ClassicalBloomFilter::new(100, 0.01);
+        let mut day2 = PartitionedBloomFilter::new(100, 0.01);
+        let mut day3 = ClassicalBloomFilter::new(100, 0.01);
+        day1.insert(b"visitor");
+        day2.insert(b"visitor");
+        day3.insert(b"someone else");
+
+        let days: [&dyn FilterDyn; 3] = [&day1, &day2, &day3];
+        assert_eq!(presence_count(&days, b"visitor"), 2);
+        assert_eq!(presence_count(&days, b"someone else"), 1);
+        assert_eq!(presence_count(&days, b"nobody"), 0);
+    }
+}
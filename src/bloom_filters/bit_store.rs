@@ -0,0 +1,94 @@
+use crate::bloom_filters::base::count_ones;
+use bit_vec::{BitBlock, BitVec};
+
+/// block type of the default `BitVec` storage, u64 on 64-bit targets
+#[cfg(target_pointer_width = "64")]
+pub type Block = u64;
+/// block type of the default `BitVec` storage, u64 on 64-bit targets
+#[cfg(not(target_pointer_width = "64"))]
+pub type Block = u32;
+
+/// order of the bits within a word of the storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOrder {
+    /// bit i is `(word >> i) & 1`, least significant first
+    Lsb0,
+    /// bit i is `(word >> (bits - 1 - i)) & 1`, most significant first
+    Msb0,
+}
+
+/// How a raw dump of the words of a bit store is to be read: bit i of
+/// the filter is bit `i % (word_bytes * 8)` in `bit_order` of word
+/// `i / (word_bytes * 8)`, words are in native endianness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryLayout {
+    pub word_bytes: usize,
+    pub num_words: usize,
+    /// `word_bytes * num_words`, the bits past m are padding
+    pub total_bytes: usize,
+    pub bit_order: BitOrder,
+}
+
+/// BitVec of len zero bits, for any block type
+pub(crate) fn zeroed<B: BitBlock>(len: usize) -> BitVec<B> {
+    let mut storage = BitVec::default();
+    storage.grow(len, false);
+    storage
+}
+
+/// `zeroed` for a length read from untrusted input, fails instead of
+/// aborting the process if the bits can't be allocated
+pub(crate) fn try_zeroed<B: BitBlock>(len: u64) -> Result<BitVec<B>, &'static str> {
+    let too_large = "filter is too large to allocate";
+    let len = usize::try_from(len).map_err(|_| too_large)?;
+    // BitVec has no fallible allocation, check up front that its blocks fit
+    Vec::<B>::new()
+        .try_reserve_exact(len.div_ceil(B::bits()))
+        .map_err(|_| too_large)?;
+    Ok(zeroed(len))
+}
+
+/// Storage backend for the bits of a filter, e.g. an mmap-ed region,
+/// a roaring bitmap or a remote store
+pub trait BitStore {
+    fn get(&self, i: usize) -> bool;
+    fn set(&mut self, i: usize, v: bool);
+    /// number of bits in the store
+    fn len(&self) -> usize;
+    /// number of bits set to 1
+    fn count_ones(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// hint that bit i is about to be read, a no-op unless overridden
+    fn prefetch(&self, _i: usize) {}
+}
+
+impl<B: BitBlock> BitStore for BitVec<B> {
+    fn get(&self, i: usize) -> bool {
+        self[i]
+    }
+
+    fn set(&mut self, i: usize, v: bool) {
+        BitVec::set(self, i, v)
+    }
+
+    fn len(&self) -> usize {
+        BitVec::len(self)
+    }
+
+    fn count_ones(&self) -> u64 {
+        count_ones(self)
+    }
+
+    #[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+    fn prefetch(&self, i: usize) {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        if let Some(block) = self.storage().get(i / B::bits()) {
+            // SAFETY: prefetch is only a hint, it never faults or reads memory
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(block as *const B as *const i8) }
+        }
+    }
+}
@@ -1,105 +1,2399 @@
-use crate::bloom_filters::Filter;
-use bit_vec::BitVec;
-use xxhash_rust::xxh3::xxh3_64_with_seed;
+use crate::bloom_filters::bit_store::{try_zeroed, zeroed};
+use crate::bloom_filters::entropy_coding::{self, Decoder, Encoder};
+use crate::bloom_filters::{
+    BitOrder, BitStore, Block, DoubleHashing, Filter, FilterDyn, FilterError, FrozenBloomFilter,
+    HashAlgo, Hashing, MemoryLayout, Probes,
+};
+use bit_vec::{BitBlock, BitVec};
+use std::io::{self, Read, Write};
+use xxhash_rust::xxh3::{xxh3_64_with_seed, Xxh3};
 
-pub struct ClassicalBloomFilter {
+const SHARD_SEED: u64 = 128;
+/// blocks OR-ed per task by the parallel merge of `par_build`
+#[cfg(feature = "rayon")]
+const PAR_MERGE_BLOCKS: usize = 1 << 14;
+/// number of buckets reported by `hash_distribution`
+pub const HASH_DISTRIBUTION_BUCKETS: usize = 16;
+/// number of regions of `region_counts`
+pub const REGIONS: usize = 64;
+/// Upper bound on k accepted by `from_parts` and the decoders, far above
+/// the k of any practical rate (2^-256)
+pub const MAX_HASH_FUNCTIONS: u64 = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassicalBloomFilter<S = BitVec<Block>> {
     /// number of bits in a Bloom filter
     m: u64,
     /// number of hash functions
     k: u64,
 
-    storage: BitVec,
+    storage: S,
+    /// algorithm or custom functions computing the base hashes
+    hashing: Hashing,
+    /// probes per region of [0, m), once enabled by `enable_region_counts`
+    region_counts: Option<Box<[u64; REGIONS]>>,
 }
 
-impl Filter for ClassicalBloomFilter {
+/// base hash function for double hashing, see `ClassicalBloomFilter::with_hash_fns`
+pub type HashFn = fn(&[u8]) -> u64;
+
+/// XOR-delta between two filters with the same m, k and hash algorithm,
+/// stored as the sorted positions of the bits that differ
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomDiff {
+    m: u64,
+    k: u64,
+    algo: HashAlgo,
+    positions: Vec<usize>,
+}
+
+impl BloomDiff {
+    /// number of bits that differ
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    /// Encodes the delta for sending it elsewhere, e.g. to a replica that
+    /// applies it with `apply_diff`. Layout: the header of
+    /// `to_sparse_bytes`, then the positions as in `to_sparse_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.positions.len());
+        write_header(&mut bytes, self.m, self.k, self.algo);
+        let mut next = 0;
+        for &idx in &self.positions {
+            write_varint(&mut bytes, idx as u64 - next);
+            next = idx as u64 + 1;
+        }
+        bytes
+    }
+
+    /// reads a delta written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FilterError> {
+        let invalid = |reason: &str| FilterError::InvalidEncoding(reason.into());
+        let (m, k, algo, mut varints) =
+            split_header(bytes).ok_or_else(|| invalid("missing header"))?;
+        let algo = HashAlgo::from_id(algo).map_err(FilterError::InvalidEncoding)?;
+        let mut positions = Vec::new();
+        let mut next = 0u64;
+        while !varints.is_empty() {
+            let idx = next
+                .checked_add(read_varint(&mut varints).map_err(invalid)?)
+                .filter(|&idx| idx < m)
+                .ok_or_else(|| invalid("position past the end of the filter"))?;
+            positions.push(idx as usize);
+            next = idx + 1;
+        }
+        Ok(Self {
+            m,
+            k,
+            algo,
+            positions,
+        })
+    }
+}
+
+/// Insert and lookup work over any `BitStore`, `Filter` itself is
+/// implemented for the default `BitVec` storage.
+impl<S: BitStore> ClassicalBloomFilter<S> {
+    /// m -- number of bits, at least one
+    /// k -- number of hash functions, at most `MAX_HASH_FUNCTIONS`
+    /// storage -- bit array of exactly m bits
+    ///
+    /// `Filter` is only implemented for the default `BitVec<Block>`
+    /// storage, bit-vec's own constructors make a `BitVec<u32>`. To get a
+    /// usable filter from raw bits see `from_blocks` and `from_bytes`.
+    pub fn from_parts(m: u64, k: u64, storage: S) -> Result<Self, FilterError> {
+        if m == 0 {
+            return Err(FilterError::ZeroBits);
+        }
+        if storage.len() != m as usize {
+            return Err(FilterError::LengthMismatch {
+                expected: m as usize,
+                actual: storage.len(),
+            });
+        }
+        if k == 0 {
+            return Err(FilterError::ZeroHashFunctions);
+        }
+        if k > MAX_HASH_FUNCTIONS {
+            return Err(FilterError::TooManyHashFunctions(k));
+        }
+        Ok(Self {
+            m,
+            k,
+            storage,
+            hashing: Hashing::Algo(HashAlgo::Xxh3),
+            region_counts: None,
+        })
+    }
+
+    fn value_probes(&self, value: &[u8]) -> Probes {
+        match self.hashing.hash_fns() {
+            Some((h1, h2)) => Probes::from_hashes(h1(value), h2(value), self.k, self.m),
+            None => Self::probes(value, self.k, self.m),
+        }
+    }
+
+    /// The k probe indices of value, generated lazily by the double
+    /// hashing recurrence without allocating, e.g. for fetching bits from
+    /// external storage. May repeat an index.
+    pub fn probe_iter<'a>(&'a self, value: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        self.value_probes(value)
+    }
+
+    fn set_probe(&mut self, idx: usize) {
+        self.storage.set(idx, true);
+        if let Some(counts) = &mut self.region_counts {
+            counts[(idx as u128 * REGIONS as u128 / self.m as u128) as usize] += 1;
+        }
+    }
+
+    pub fn insert(&mut self, value: &[u8]) {
+        for idx in self.value_probes(value) {
+            self.set_probe(idx);
+        }
+    }
+
+    pub fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(self.k as usize);
+        for idx in self.value_probes(value) {
+            self.set_probe(idx);
+            indices.push(idx);
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    /// Inserts values by their precomputed base hashes, bypassing the
+    /// filter's hashing: the probes are `h1 + i * h2 (mod m)` just as for
+    /// `insert`. Hash quality is up to the caller, `base_hashes` gives the
+    /// pair `insert` would have used.
+    pub fn insert_hash_pairs(&mut self, pairs: &[(u64, u64)]) {
+        for &(h1, h2) in pairs {
+            for idx in Probes::from_hashes(h1, h2, self.k, self.m) {
+                self.set_probe(idx);
+            }
+        }
+    }
+
+    /// Starts counting the probes of every insert per each of `REGIONS`
+    /// equal-width regions of [0, m), a cheap way to spot hot regions
+    /// without scanning the bits. Inserts before this aren't counted, nor
+    /// are bits merged in from other filters.
+    pub fn enable_region_counts(&mut self) {
+        self.region_counts
+            .get_or_insert_with(|| Box::new([0; REGIONS]));
+    }
+
+    /// probes counted per region, all zero unless `enable_region_counts`
+    pub fn region_counts(&self) -> [u64; REGIONS] {
+        self.region_counts
+            .as_deref()
+            .copied()
+            .unwrap_or([0; REGIONS])
+    }
+
+    /// lookup by the base hashes of a value, see `insert_hash_pairs`
+    pub fn lookup_hash_pair(&self, h1: u64, h2: u64) -> bool {
+        Probes::from_hashes(h1, h2, self.k, self.m).all(|idx| self.storage.get(idx))
+    }
+
+    /// Diagnostic: average number of distinct bits probed per value over
+    /// the sample. Double hashing can hit the same index more than once,
+    /// so this is <= k.
+    pub fn effective_k_sample(&self, sample: &[&[u8]]) -> f64 {
+        if sample.is_empty() {
+            return self.k as f64;
+        }
+        let mut indices = Vec::with_capacity(self.k as usize);
+        let mut distinct = 0;
+        for value in sample {
+            indices.clear();
+            indices.extend(self.value_probes(value));
+            indices.sort_unstable();
+            indices.dedup();
+            distinct += indices.len();
+        }
+        distinct as f64 / sample.len() as f64
+    }
+
+    /// Diagnostic: histogram of where the probes of the sample land,
+    /// over `HASH_DISTRIBUTION_BUCKETS` equal-width buckets of [0, m).
+    /// A skewed histogram points at poorly hashing inputs.
+    pub fn hash_distribution(&self, sample: &[&[u8]]) -> Vec<u64> {
+        let mut histogram = vec![0; HASH_DISTRIBUTION_BUCKETS];
+        for value in sample {
+            for idx in self.value_probes(value) {
+                let bucket = idx as u128 * HASH_DISTRIBUTION_BUCKETS as u128 / self.m as u128;
+                histogram[bucket as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// number of the k probed bits that are set, k means a probable member,
+    /// anything less a definite non-member
+    pub fn probe_match_count(&self, value: &[u8]) -> u32 {
+        self.value_probes(value)
+            .filter(|&idx| self.storage.get(idx))
+            .count() as u32
+    }
+
+    /// Fraction of set bits in each of `buckets` equal-width ranges of
+    /// [0, m), a downsampled view for spotting hotspots. Empty for 0
+    /// buckets.
+    pub fn density_map(&self, buckets: usize) -> Vec<f64> {
+        let m = self.storage.len();
+        (0..buckets)
+            .map(|bucket| {
+                let start = bucket * m / buckets;
+                let end = (bucket + 1) * m / buckets;
+                if start == end {
+                    return 0.0;
+                }
+                let set = (start..end).filter(|&idx| self.storage.get(idx)).count();
+                set as f64 / (end - start) as f64
+            })
+            .collect()
+    }
+
+    /// With the `prefetch` feature every probed word is prefetched before
+    /// the probe loop, so on filters that don't fit in cache the k memory
+    /// accesses overlap instead of being serialized.
+    pub fn lookup(&self, value: &[u8]) -> bool {
+        let mut probes = self.value_probes(value);
+        if cfg!(feature = "prefetch") {
+            for idx in probes.clone() {
+                self.storage.prefetch(idx);
+            }
+        }
+        probes.all(|idx| self.storage.get(idx))
+    }
+}
+
+impl<S> DoubleHashing for ClassicalBloomFilter<S> {}
+
+/// The block (word) size of the default storage is `Block`, these work
+/// with any, e.g. `ClassicalBloomFilter::<BitVec<u32>>` on 32-bit targets.
+impl<B: BitBlock> ClassicalBloomFilter<BitVec<B>> {
     /// n -- number of elements to insert
     /// f -- the false positive rate
-    fn new(n: u32, f: f64) -> Self {
-        let m = Self::calculate_m(f, n);
+    pub fn new_with_blocks(n: u32, f: f64) -> Self {
+        let m = <ClassicalBloomFilter>::calculate_m(f, n);
         Self {
             m,
-            k: Self::calculate_k(m, n),
-            storage: BitVec::from_elem(m as usize, false),
+            k: <ClassicalBloomFilter>::calculate_k(m, n),
+            storage: zeroed(m as usize),
+            hashing: Hashing::Algo(HashAlgo::Xxh3),
+            region_counts: None,
         }
     }
 
-    fn insert(&mut self, value: &[u8]) {
-        let hash1 = xxh3_64_with_seed(value, 0) % self.m;
-        let hash2 = xxh3_64_with_seed(value, 64) % self.m;
-        for i in 0..self.k {
-            let idx = ((hash1 + i * hash2) % self.m) as usize;
-            self.storage.set(idx, true);
-        }
+    /// bytes allocated for the bit array, a multiple of the block size
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of_val(self.storage.storage())
     }
 
-    fn lookup(&self, value: &[u8]) -> bool {
-        let hash1 = xxh3_64_with_seed(value, 0) % self.m;
-        let hash2 = xxh3_64_with_seed(value, 64) % self.m;
-        for i in 0..self.k {
-            let idx = ((hash1 + i * hash2) % self.m) as usize;
-            if self.storage.get(idx) == Some(false) {
-                return false;
+    /// The blocks backing the bit array without copying. Bit i is bit
+    /// `i % B::bits()` (least significant first) of block `i / B::bits()`,
+    /// the bits past `num_bits()` in the last block are zero. See also
+    /// `memory_layout`.
+    pub fn as_bit_slice(&self) -> &[B] {
+        self.storage.storage()
+    }
+
+    /// Rebuilds a filter from blocks laid out as in `as_bit_slice`,
+    /// exactly ceil(m / B::bits()) of them with the bits past m zero.
+    pub fn from_blocks(m: u64, k: u64, blocks: &[B]) -> Result<Self, FilterError> {
+        let invalid = |reason: &str| FilterError::InvalidEncoding(reason.into());
+        let mut storage = try_zeroed::<B>(m).map_err(invalid)?;
+        if blocks.len() != storage.storage().len() {
+            return Err(invalid("number of blocks doesn't match m"));
+        }
+        let tail = m as usize % B::bits();
+        if let (Some(&last), true) = (blocks.last(), tail != 0) {
+            if last >> tail != B::zero() {
+                return Err(invalid("bit set past the end of the filter"));
             }
         }
-        true
+        // SAFETY: the bits past m are checked to be zero above, which is
+        // what BitVec relies on
+        unsafe { storage.storage_mut() }.copy_from_slice(blocks);
+        Self::from_parts(m, k, storage)
     }
 
-    fn get_size(&self) -> usize {
-        self.storage.len()
+    /// layout of the words backing the bit array, for FFI and mmap
+    pub fn memory_layout(&self) -> MemoryLayout {
+        let word_bytes = std::mem::size_of::<B>();
+        let num_words = self.storage.storage().len();
+        MemoryLayout {
+            word_bytes,
+            num_words,
+            total_bytes: self.memory_bytes(),
+            bit_order: BitOrder::Lsb0,
+        }
+    }
+
+    /// The bit array as ceil(m / 8) bytes, independent of the block size:
+    /// bit i is bit `7 - i % 8` (most significant first) of byte `i / 8`
+    /// and the unused trailing bits of the last byte are zero.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.storage.to_bytes()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rand::distributions::Uniform;
-    use rand::{thread_rng, Rng};
-    use std::collections::HashSet;
+impl ClassicalBloomFilter {
+    /// Reparametrizes the filter for n elements at rate f. All inserted
+    /// items are lost. The existing allocation is reused (and zeroed) when
+    /// it can hold the new m bits, otherwise a new one is made.
+    pub fn reset_with(&mut self, n: u32, f: f64) {
+        let m = Self::calculate_m(f, n);
+        self.m = m;
+        self.k = Self::calculate_k(m, n);
+        if m as usize <= self.storage.capacity() {
+            self.storage.truncate(0);
+            self.storage.grow(m as usize, false);
+        } else {
+            self.storage = zeroed(m as usize);
+        }
+        if let Some(counts) = &mut self.region_counts {
+            counts.fill(0);
+        }
+    }
 
-    #[test]
-    fn simple_check() {
-        let mut bf = ClassicalBloomFilter::new(10, 0.01);
-        bf.insert(&1u32.to_be_bytes());
-        bf.insert(&10u32.to_be_bytes());
-        bf.insert(&30u32.to_be_bytes());
+    /// Like `new`, but the two base hashes of double hashing are computed
+    /// by h1 and h2 instead of the seeded xxh3, the k indices are still
+    /// `h1 + i * h2 (mod m)`. Hash quality is up to the caller. Function
+    /// pointers can't be identified, so the filter is never compatible with
+    /// another one, not even one built from the same functions, and can't
+    /// be serialized.
+    pub fn with_hash_fns(n: u32, f: f64, h1: HashFn, h2: HashFn) -> Self {
+        Self {
+            hashing: Hashing::Custom(h1, h2),
+            ..Self::new(n, f)
+        }
+    }
 
-        let res = bf.lookup(&1u32.to_be_bytes());
-        assert!(res, "stored value is not found!");
+    /// like `new`, with the base hashes computed by algo
+    pub fn with_algo(n: u32, f: f64, algo: HashAlgo) -> Self {
+        Self {
+            hashing: Hashing::Algo(algo),
+            ..Self::new(n, f)
+        }
+    }
 
-        let res = bf.lookup(&10u32.to_be_bytes());
-        assert!(res, "stored value is not found!");
+    /// Migration helper rebuilding a filter for n elements at rate f under
+    /// new_algo. Bits hashed by old_algo can't be moved to other indices,
+    /// so items must yield every item of the old filter, anything missing
+    /// is lost. old_algo doesn't change the result, it only exists to be
+    /// logged along with new_algo by the `tracing` feature.
+    pub fn rehash<I: IntoIterator<Item = Vec<u8>>>(
+        old_algo: HashAlgo,
+        new_algo: HashAlgo,
+        n: u32,
+        f: f64,
+        items: I,
+    ) -> Self {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?old_algo, ?new_algo, n, f, "rehashing bloom filter");
+        #[cfg(not(feature = "tracing"))]
+        let _ = old_algo;
+        let mut filter = Self::with_algo(n, f, new_algo);
+        for item in items {
+            filter.insert(&item);
+        }
+        filter
+    }
 
-        let res = bf.lookup(&30u32.to_be_bytes());
-        assert!(res, "stored value is not found!");
+    /// m -- number of bits
+    /// k -- number of hash functions
+    /// probs -- probability of every bit being set, e.g. aggregated over
+    /// several noisy filters, bit i is set when `probs[i] >= threshold`
+    pub fn from_bit_probabilities(
+        m: u64,
+        k: u64,
+        probs: &[f64],
+        threshold: f64,
+    ) -> Result<Self, FilterError> {
+        if probs.len() != m as usize {
+            return Err(FilterError::LengthMismatch {
+                expected: m as usize,
+                actual: probs.len(),
+            });
+        }
+        let mut storage = zeroed(m as usize);
+        for (idx, &prob) in probs.iter().enumerate() {
+            if prob >= threshold {
+                storage.set(idx, true);
+            }
+        }
+        Self::from_parts(m, k, storage)
+    }
 
-        let res = bf.lookup(&45u32.to_be_bytes());
-        assert!(!res, "not stored value is found!");
+    /// Rebuilds a filter from exactly ceil(m / 8) bytes laid out as in
+    /// `as_bytes`, whatever the block size they were produced with
+    pub fn from_bytes(m: u64, k: u64, bytes: &[u8]) -> Result<Self, FilterError> {
+        if bytes.len() as u64 != m.div_ceil(8) {
+            return Err(FilterError::InvalidEncoding(
+                "number of bytes doesn't match m".into(),
+            ));
+        }
+        let storage =
+            storage_from_bytes(bytes, m).map_err(|e| FilterError::InvalidEncoding(e.into()))?;
+        Self::from_parts(m, k, storage)
     }
 
-    #[test]
-    fn verify_false_positive_rate() {
-        let mut bf = ClassicalBloomFilter::new(10u32.pow(7), 0.02);
-        let mut track_inserted = HashSet::new();
+    /// converts into a read-only filter, see `FrozenBloomFilter`
+    pub fn freeze(self) -> FrozenBloomFilter {
+        let blocks = self.storage.storage().to_vec().into_boxed_slice();
+        FrozenBloomFilter::from_blocks(self.m, self.k, blocks, self.hashing)
+    }
 
-        let mut rng = thread_rng();
-        let distribution = Uniform::new_inclusive(0, 10u64.pow(12));
-        for _ in 0..10u32.pow(7) {
-            let value = rng.sample(distribution).to_be_bytes();
-            bf.insert(&value);
-            track_inserted.insert(value);
+    /// hash algorithm for serialized headers
+    fn serialized_algo(&self) -> Result<HashAlgo, FilterError> {
+        self.hashing.algo().ok_or(FilterError::CustomHashFns)
+    }
+
+    /// sets the hash algorithm read from a serialized header
+    fn hashed_by(mut self, algo_id: u8) -> Result<Self, FilterError> {
+        let algo = HashAlgo::from_id(algo_id).map_err(FilterError::InvalidEncoding)?;
+        self.hashing = Hashing::Algo(algo);
+        Ok(self)
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), FilterError> {
+        if self.m != other.m || self.k != other.k || self.hashing != other.hashing {
+            return Err(FilterError::IncompatibleParameters);
         }
+        Ok(())
+    }
 
-        let mut false_positive = 0;
-        for _ in 0..10u32.pow(6) {
-            let value = rng.sample(distribution).to_be_bytes();
-            let found = bf.lookup(&value);
-            if found && track_inserted.get(&value).is_none() {
-                false_positive += 1;
+    /// xxh3 over m, k and the bit array: equal filters always share a
+    /// checksum, different checksums mean the filters are different
+    pub fn storage_checksum(&self) -> u64 {
+        let mut hasher = Xxh3::new();
+        hasher.update(&self.m.to_le_bytes());
+        hasher.update(&self.k.to_le_bytes());
+        hasher.update(&self.storage.to_bytes());
+        hasher.digest()
+    }
+
+    /// Writes the filter with its bit array arithmetic coded using the
+    /// measured fill ratio, which gets close to the entropy bound
+    /// `m * H(fill_ratio)` bits. Layout (little endian): m: u64, k: u64,
+    /// hash algorithm id: u8, probability of a 0 bit scaled to 2^16: u16,
+    /// payload length: u64, payload. Fails for custom hash functions.
+    pub fn write_entropy_coded<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let algo = self
+            .serialized_algo()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let prob_zero = entropy_coding::zero_probability(self.fill_ratio());
+        let mut encoder = Encoder::new(prob_zero);
+        for bit in self.storage.iter() {
+            encoder.encode(bit);
+        }
+        let payload = encoder.finish();
+
+        w.write_all(&self.m.to_le_bytes())?;
+        w.write_all(&self.k.to_le_bytes())?;
+        w.write_all(&[algo.id()])?;
+        w.write_all(&prob_zero.to_le_bytes())?;
+        w.write_all(&(payload.len() as u64).to_le_bytes())?;
+        w.write_all(&payload)
+    }
+
+    /// reads a filter written by `write_entropy_coded`
+    pub fn read_entropy_coded<R: Read>(r: &mut R) -> io::Result<Self> {
+        let m = read_u64(r)?;
+        let k = read_u64(r)?;
+        let algo = read_u8(r)?;
+        let mut prob = [0u8; 2];
+        r.read_exact(&mut prob)?;
+        let prob_zero = u16::from_le_bytes(prob);
+        let len = read_u64(r)?;
+        // the length is untrusted, only allocate what is actually there
+        let mut payload = Vec::new();
+        r.take(len).read_to_end(&mut payload)?;
+        if payload.len() as u64 != len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "payload is shorter than its length",
+            ));
+        }
+
+        let mut decoder = Decoder::new(prob_zero, &payload);
+        let mut storage =
+            try_zeroed(m).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        for idx in 0..m as usize {
+            if decoder.decode() {
+                storage.set(idx, true);
             }
         }
+        Self::from_parts(m, k, storage)
+            .and_then(|filter| filter.hashed_by(algo))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
 
-        dbg!("regular", false_positive);
-        // check that false positive rate is ~2%
-        assert!(19900 < false_positive && false_positive < 21000);
+    /// Writes the filter without the trailing all-zero bytes of the bit
+    /// array. Inserted values probe all of [0, m) evenly, so the highest
+    /// set bit is near m even for a nearly empty filter and this is about
+    /// as large as `as_bytes`. It only pays off when the high bits happen
+    /// to be clear, sparse filters are much smaller as `to_sparse_bytes`.
+    /// Layout (little endian): m: u64, k: u64, hash algorithm id: u8,
+    /// number of written bytes: u64, then the bytes of `as_bytes` up to
+    /// the one holding the highest set bit. Fails for custom hash
+    /// functions.
+    pub fn write_compact<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let algo = self
+            .serialized_algo()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let bytes = self.as_bytes();
+        let used = bytes
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |pos| pos + 1);
+
+        w.write_all(&self.m.to_le_bytes())?;
+        w.write_all(&self.k.to_le_bytes())?;
+        w.write_all(&[algo.id()])?;
+        w.write_all(&(used as u64).to_le_bytes())?;
+        w.write_all(&bytes[..used])
+    }
+
+    /// reads a filter written by `write_compact`, the bits that weren't
+    /// written are zero
+    pub fn read_compact<R: Read>(r: &mut R) -> io::Result<Self> {
+        let m = read_u64(r)?;
+        let k = read_u64(r)?;
+        let algo = read_u8(r)?;
+        let used = read_u64(r)? as usize;
+        if used > (m as usize).div_ceil(8) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "more bytes than the filter has bits",
+            ));
+        }
+        // bounded by m only, which is untrusted too
+        let mut bytes = Vec::new();
+        r.take(used as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != used {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "fewer bytes than the header claims",
+            ));
+        }
+
+        let storage = storage_from_bytes(&bytes, m)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Self::from_parts(m, k, storage)
+            .and_then(|filter| filter.hashed_by(algo))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// The filter as a one row Arrow record batch with the columns
+    /// m: UInt64, k: UInt64, algo: UInt8 holding the hash algorithm id and
+    /// bits: Binary holding `as_bytes`, e.g. for storing filters in
+    /// Parquet. Fails for custom hash functions.
+    #[cfg(feature = "arrow")]
+    pub fn to_arrow(&self) -> Result<arrow_array::RecordBatch, FilterError> {
+        use arrow_array::{BinaryArray, UInt64Array, UInt8Array};
+        use std::sync::Arc;
+
+        let algo = self.serialized_algo()?.id();
+        let bytes = self.as_bytes();
+        let batch = arrow_array::RecordBatch::try_new(
+            Arc::new(arrow_schema()),
+            vec![
+                Arc::new(UInt64Array::from(vec![self.m])),
+                Arc::new(UInt64Array::from(vec![self.k])),
+                Arc::new(UInt8Array::from(vec![algo])),
+                Arc::new(BinaryArray::from(vec![bytes.as_slice()])),
+            ],
+        )
+        .expect("columns match the schema");
+        Ok(batch)
+    }
+
+    /// reads the filter in the first row of a batch made by `to_arrow`
+    #[cfg(feature = "arrow")]
+    pub fn from_arrow(batch: &arrow_array::RecordBatch) -> Result<Self, FilterError> {
+        use arrow_array::{Array, BinaryArray, UInt64Array, UInt8Array};
+
+        fn column<'a, T: Array + 'static>(
+            batch: &'a arrow_array::RecordBatch,
+            name: &str,
+        ) -> Result<&'a T, FilterError> {
+            batch
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<T>())
+                .filter(|column| !column.is_empty() && column.is_valid(0))
+                .ok_or_else(|| FilterError::InvalidEncoding(format!("missing column {name}")))
+        }
+
+        let m = column::<UInt64Array>(batch, "m")?.value(0);
+        let k = column::<UInt64Array>(batch, "k")?.value(0);
+        let algo = column::<UInt8Array>(batch, "algo")?.value(0);
+        let bytes = column::<BinaryArray>(batch, "bits")?.value(0);
+        let storage =
+            storage_from_bytes(bytes, m).map_err(|e| FilterError::InvalidEncoding(e.into()))?;
+        Self::from_parts(m, k, storage)?.hashed_by(algo)
+    }
+
+    /// Sparse wire format, the set bit positions instead of the bit array,
+    /// smaller than `as_bytes` below roughly 1/8th fill. Layout: m: u64
+    /// and k: u64 (little endian), hash algorithm id: u8, then for every
+    /// set bit in ascending order the gap to the previous one (to -1 for
+    /// the first) as an LEB128 varint. Fails for custom hash functions.
+    pub fn to_sparse_bytes(&self) -> Result<Vec<u8>, FilterError> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        write_header(&mut bytes, self.m, self.k, self.serialized_algo()?);
+        let mut next = 0;
+        for (block_idx, block) in self.storage.blocks().enumerate() {
+            let mut block = block;
+            while block != 0 {
+                let idx =
+                    (block_idx * Block::BITS as usize + block.trailing_zeros() as usize) as u64;
+                write_varint(&mut bytes, idx - next);
+                next = idx + 1;
+                block &= block - 1;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// reads a filter written by `to_sparse_bytes`
+    pub fn from_sparse_bytes(bytes: &[u8]) -> Result<Self, FilterError> {
+        let invalid = |reason: &str| FilterError::InvalidEncoding(reason.into());
+        let (m, k, algo, mut varints) =
+            split_header(bytes).ok_or_else(|| invalid("missing header"))?;
+
+        let mut storage = try_zeroed(m).map_err(invalid)?;
+        let mut next = 0u64;
+        while !varints.is_empty() {
+            let idx = next
+                .checked_add(read_varint(&mut varints).map_err(invalid)?)
+                .filter(|&idx| idx < m)
+                .ok_or_else(|| invalid("bit set past the end of the filter"))?;
+            storage.set(idx as usize, true);
+            next = idx + 1;
+        }
+        Self::from_parts(m, k, storage)?.hashed_by(algo)
+    }
+
+    /// number of bits that differ between the two filters
+    pub fn hamming_distance(&self, other: &Self) -> Result<u64, FilterError> {
+        self.check_compatible(other)?;
+        let blocks = self.storage.blocks().zip(other.storage.blocks());
+        Ok(blocks.map(|(a, b)| (a ^ b).count_ones() as u64).sum())
+    }
+
+    /// shard the value is routed to by `build_shards`
+    pub fn shard_of(value: &[u8], num_shards: usize) -> usize {
+        // separate seed so routing isn't correlated with the probe hashes
+        (xxh3_64_with_seed(value, SHARD_SEED) % num_shards as u64) as usize
+    }
+
+    /// builds num_shards independent filters, each sized for n_per_shard
+    /// elements at rate f, routing every item to `shard_of(item)`
+    pub fn build_shards<I: IntoIterator<Item = Vec<u8>>>(
+        num_shards: usize,
+        n_per_shard: u32,
+        f: f64,
+        items: I,
+    ) -> Vec<ClassicalBloomFilter> {
+        assert!(num_shards >= 1, "at least one shard is required");
+        let mut shards: Vec<ClassicalBloomFilter> = (0..num_shards)
+            .map(|_| ClassicalBloomFilter::new(n_per_shard, f))
+            .collect();
+        for item in items {
+            shards[Self::shard_of(&item, num_shards)].insert(&item);
+        }
+        shards
+    }
+
+    /// Builds the filter for n elements at rate f in parallel: every rayon
+    /// worker thread fills its own filter, so there are at most
+    /// `rayon::current_num_threads()` of them however the input is split,
+    /// then the bit range is split in chunks that are OR-ed across all
+    /// thread filters in parallel too. The result is bit for bit the
+    /// filter a sequential build would give.
+    #[cfg(feature = "rayon")]
+    pub fn par_build<I, T>(n: u32, f: f64, iter: I) -> Self
+    where
+        I: rayon::iter::ParallelIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        use rayon::prelude::*;
+        use std::sync::Mutex;
+
+        let threads = rayon::current_num_threads();
+        let slots: Vec<Mutex<Self>> = (0..threads).map(|_| Mutex::new(Self::new(n, f))).collect();
+        iter.for_each(|item| {
+            // only this thread uses its slot, the lock is never contended
+            let slot = rayon::current_thread_index().unwrap_or(0) % threads;
+            let mut filter = slots[slot].lock().unwrap_or_else(|e| e.into_inner());
+            filter.insert(item.as_ref());
+        });
+        let mut filters: Vec<Self> = slots
+            .into_iter()
+            .map(|slot| slot.into_inner().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        let mut merged = filters.pop().unwrap_or_else(|| Self::new(n, f));
+        // SAFETY: the filters all have m bits, so OR-ing their blocks keeps
+        // the unused bits past m zero, which is what BitVec relies on
+        let blocks = unsafe { merged.storage.storage_mut() };
+        blocks
+            .par_chunks_mut(PAR_MERGE_BLOCKS)
+            .enumerate()
+            .for_each(|(chunk, out)| {
+                let start = chunk * PAR_MERGE_BLOCKS;
+                for filter in &filters {
+                    let blocks = &filter.storage.storage()[start..start + out.len()];
+                    for (out, block) in out.iter_mut().zip(blocks) {
+                        *out |= block;
+                    }
+                }
+            });
+        merged
+    }
+
+    /// ORs other into self, afterwards self contains the items of both
+    pub fn merge_into(&mut self, other: &Self) -> Result<(), FilterError> {
+        self.check_compatible(other)?;
+        self.storage.or(&other.storage);
+        Ok(())
+    }
+
+    /// Estimated |A ∩ B| / |A| for A = self and B = other, from
+    /// |A ∩ B| = |A| + |B| - |A ∪ B| over the cardinality estimates,
+    /// clamped to [0, 1]. 0 when self is empty.
+    pub fn overlap_ratio(&self, other: &Self) -> Result<f64, FilterError> {
+        let mut union = self.clone();
+        union.merge_into(other)?;
+        let count = self.estimate_count() as f64;
+        if count == 0.0 {
+            return Ok(0.0);
+        }
+        let intersection = count + other.estimate_count() as f64 - union.estimate_count() as f64;
+        Ok((intersection / count).clamp(0.0, 1.0))
+    }
+
+    /// ORs all filters into a new one
+    pub fn union_many(filters: &[Self]) -> Result<Self, FilterError> {
+        let (first, rest) = filters.split_first().ok_or(FilterError::EmptyInput)?;
+        let mut union = first.clone();
+        for filter in rest {
+            union.merge_into(filter)?;
+        }
+        Ok(union)
+    }
+
+    /// Decodes every chunk as written by `write_compact` and ORs it into
+    /// self, one chunk at a time. Stops at the first chunk that can't be
+    /// decoded or doesn't match m and k, the ones before it stay merged.
+    pub fn fold_bytes<I: Iterator<Item = Vec<u8>>>(
+        &mut self,
+        chunks: I,
+    ) -> Result<(), FilterError> {
+        for chunk in chunks {
+            let filter = Self::read_compact(&mut chunk.as_slice())
+                .map_err(|e| FilterError::InvalidEncoding(e.to_string()))?;
+            self.merge_into(&filter)?;
+        }
+        Ok(())
+    }
+
+    /// Unions filters of different sizes into one filter at rate f. Bits
+    /// can't be rehashed into a different m, so `item_sources[i]` has to
+    /// yield the items of `filters[i]`, a missing source is an error. The
+    /// result is sized for the larger of the filters' summed estimated
+    /// counts and the number of supplied items, saturated filters have no
+    /// usable estimate and only count through their items.
+    pub fn merge_resizing<I: IntoIterator<Item = Vec<u8>>>(
+        filters: &[&Self],
+        item_sources: Vec<I>,
+        f: f64,
+    ) -> Result<Self, FilterError> {
+        if filters.is_empty() {
+            return Err(FilterError::EmptyInput);
+        }
+        if item_sources.len() < filters.len() {
+            return Err(FilterError::MissingItemSource {
+                filter: item_sources.len(),
+            });
+        }
+        let items: Vec<Vec<u8>> = item_sources.into_iter().flatten().collect();
+        let estimated = summed_estimate(filters.iter().copied());
+        let n = estimated.max(items.len() as u64).clamp(1, u32::MAX as u64) as u32;
+
+        let mut merged = Self::new(n, f);
+        for item in &items {
+            merged.insert(item);
+        }
+        Ok(merged)
+    }
+
+    /// Unions a and b. Compatible filters are ORed, which is O(m / 64).
+    /// Otherwise bits can't be mixed, so `items_if_incompatible` is called
+    /// and all its items are rehashed into a new filter sized for the
+    /// summed estimated counts of the unsaturated filters (or the number
+    /// of items, if larger), at the stricter of the two rates taken as
+    /// 2^-k. That costs k hashes per item plus holding the items in
+    /// memory; no items is an error.
+    pub fn combine(
+        a: &Self,
+        b: &Self,
+        items_if_incompatible: impl FnOnce() -> Vec<Vec<u8>>,
+    ) -> Result<Self, FilterError> {
+        if a.check_compatible(b).is_ok() {
+            let mut union = a.clone();
+            union.merge_into(b)?;
+            return Ok(union);
+        }
+
+        let items = items_if_incompatible();
+        if items.is_empty() {
+            return Err(FilterError::EmptyInput);
+        }
+        let estimated = summed_estimate([a, b].into_iter());
+        let n = estimated.max(items.len() as u64).clamp(1, u32::MAX as u64) as u32;
+        let f = 0.5f64.powi(a.k.max(b.k) as i32);
+
+        let mut combined = Self::new(n, f);
+        for item in &items {
+            combined.insert(item);
+        }
+        Ok(combined)
+    }
+
+    /// produces the delta that turns `self` into `other`
+    pub fn diff(&self, other: &Self) -> Result<BloomDiff, FilterError> {
+        self.check_compatible(other)?;
+        let mut positions = Vec::new();
+        let blocks = self.storage.blocks().zip(other.storage.blocks());
+        for (block_idx, (a, b)) in blocks.enumerate() {
+            let mut delta = a ^ b;
+            while delta != 0 {
+                positions.push(block_idx * Block::BITS as usize + delta.trailing_zeros() as usize);
+                delta &= delta - 1;
+            }
+        }
+        let algo = self
+            .hashing
+            .algo()
+            .expect("compatible filters have an algorithm");
+        Ok(BloomDiff {
+            m: self.m,
+            k: self.k,
+            algo,
+            positions,
+        })
+    }
+
+    /// flips every bit recorded in the delta
+    pub fn apply_diff(&mut self, diff: &BloomDiff) -> Result<(), FilterError> {
+        if self.m != diff.m || self.k != diff.k || self.hashing != Hashing::Algo(diff.algo) {
+            return Err(FilterError::IncompatibleParameters);
+        }
+        for &idx in &diff.positions {
+            let bit = self.storage[idx];
+            self.storage.set(idx, !bit);
+        }
+        Ok(())
+    }
+}
+
+/// Summed `estimate_count` of the filters for sizing a rebuild. A
+/// saturated filter estimates u64::MAX, which says nothing about its
+/// number of items, so it is left out.
+fn summed_estimate<'a>(filters: impl Iterator<Item = &'a ClassicalBloomFilter>) -> u64 {
+    filters
+        .map(|filter| filter.estimate_count())
+        .filter(|&estimate| estimate != u64::MAX)
+        .fold(0, u64::saturating_add)
+}
+
+/// m bits from at most ceil(m / 8) bytes laid out as in `as_bytes`,
+/// missing trailing bytes are zero
+fn storage_from_bytes(bytes: &[u8], m: u64) -> Result<BitVec<Block>, &'static str> {
+    if bytes.len() > (m as usize).div_ceil(8) {
+        return Err("more bytes than the filter has bits");
+    }
+    let mut storage = try_zeroed(m)?;
+    for (idx, byte) in bytes.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) == 0 {
+                continue;
+            }
+            let pos = idx * 8 + bit;
+            if pos >= m as usize {
+                return Err("bit set past the end of the filter");
+            }
+            storage.set(pos, true);
+        }
+    }
+    Ok(storage)
+}
+
+#[cfg(feature = "arrow")]
+fn arrow_schema() -> arrow_schema::Schema {
+    use arrow_schema::{DataType, Field};
+    arrow_schema::Schema::new(vec![
+        Field::new("m", DataType::UInt64, false),
+        Field::new("k", DataType::UInt64, false),
+        Field::new("algo", DataType::UInt8, false),
+        Field::new("bits", DataType::Binary, false),
+    ])
+}
+
+impl Filter for ClassicalBloomFilter {
+    /// n -- number of elements to insert
+    /// f -- the false positive rate
+    fn new(n: u32, f: f64) -> Self {
+        let m = Self::calculate_m(f, n);
+        let k = Self::calculate_k(m, n);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(n, f, m, k, "created classical bloom filter");
+        Self {
+            m,
+            k,
+            storage: zeroed(m as usize),
+            hashing: Hashing::Algo(HashAlgo::Xxh3),
+            region_counts: None,
+        }
+    }
+
+    fn insert(&mut self, value: &[u8]) {
+        ClassicalBloomFilter::insert(self, value)
+    }
+
+    fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize> {
+        ClassicalBloomFilter::insert_tracked(self, value)
+    }
+
+    fn lookup(&self, value: &[u8]) -> bool {
+        ClassicalBloomFilter::lookup(self, value)
+    }
+
+    fn get_size(&self) -> usize {
+        self.storage.len()
+    }
+
+    fn num_bits(&self) -> u64 {
+        self.m
+    }
+
+    fn num_hashes(&self) -> u64 {
+        self.k
+    }
+
+    fn set_bits(&self) -> u64 {
+        self.storage.count_ones()
+    }
+}
+
+/// Unions dynamically typed filters into a classical one. Only classical
+/// filters with the same m, k and hash functions can be combined, they're
+/// downcast via `FilterDyn::as_any`, anything else is incompatible.
+pub fn union_dyn(filters: &[&dyn FilterDyn]) -> Result<ClassicalBloomFilter, FilterError> {
+    fn classical(filter: &dyn FilterDyn) -> Result<&ClassicalBloomFilter, FilterError> {
+        filter
+            .as_any()
+            .downcast_ref()
+            .ok_or(FilterError::IncompatibleParameters)
+    }
+
+    let (first, rest) = filters.split_first().ok_or(FilterError::EmptyInput)?;
+    let mut union = classical(*first)?.clone();
+    for filter in rest {
+        if filter.num_bits() != union.m || filter.num_hashes() != union.k {
+            return Err(FilterError::IncompatibleParameters);
+        }
+        union.merge_into(classical(*filter)?)?;
+    }
+    Ok(union)
+}
+
+/// length of the header of the sparse and delta formats
+const HEADER_LEN: usize = 17;
+
+/// m and k as little endian u64s, then the hash algorithm id
+fn write_header(bytes: &mut Vec<u8>, m: u64, k: u64, algo: HashAlgo) {
+    bytes.extend_from_slice(&m.to_le_bytes());
+    bytes.extend_from_slice(&k.to_le_bytes());
+    bytes.push(algo.id());
+}
+
+/// m, k and the hash algorithm id from the header written by
+/// `write_header`, and the bytes after it
+fn split_header(bytes: &[u8]) -> Option<(u64, u64, u8, &[u8])> {
+    let m = u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+    let k = u64::from_le_bytes(bytes.get(8..16)?.try_into().ok()?);
+    let algo = *bytes.get(16)?;
+    Some((m, k, algo, &bytes[HEADER_LEN..]))
+}
+
+/// appends value as an LEB128 varint
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// reads an LEB128 varint from the front of bytes
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, &'static str> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = bytes.split_first().ok_or("truncated varint")?;
+        *bytes = rest;
+        if shift > 63 {
+            return Err("varint overflows u64");
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut word = [0u8; 8];
+    r.read_exact(&mut word)?;
+    Ok(u64::from_le_bytes(word))
+}
+
+/// messages of the tracing events emitted while running f
+#[cfg(all(test, feature = "tracing"))]
+pub(crate) fn captured_events(f: impl FnOnce()) -> Vec<String> {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    struct CaptureEvents(Arc<Mutex<Vec<String>>>);
+
+    struct Message(Option<String>);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = Some(format!("{value:?}"));
+            }
+        }
+    }
+
+    impl Subscriber for CaptureEvents {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut message = Message(None);
+            event.record(&mut message);
+            self.0.lock().unwrap().extend(message.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    tracing::subscriber::with_default(CaptureEvents(events.clone()), f);
+    let events = events.lock().unwrap();
+    events.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom_filters::base_hashes;
+    use rand::distributions::Uniform;
+    use rand::{thread_rng, Rng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn simple_check() {
+        let mut bf = ClassicalBloomFilter::new(10, 0.01);
+        bf.insert(&1u32.to_be_bytes());
+        bf.insert(&10u32.to_be_bytes());
+        bf.insert(&30u32.to_be_bytes());
+
+        let res = bf.lookup(&1u32.to_be_bytes());
+        assert!(res, "stored value is not found!");
+
+        let res = bf.lookup(&10u32.to_be_bytes());
+        assert!(res, "stored value is not found!");
+
+        let res = bf.lookup(&30u32.to_be_bytes());
+        assert!(res, "stored value is not found!");
+
+        let res = bf.lookup(&45u32.to_be_bytes());
+        assert!(!res, "not stored value is found!");
+    }
+
+    #[test]
+    fn verify_false_positive_rate() {
+        let mut bf = ClassicalBloomFilter::new(10u32.pow(7), 0.02);
+        let mut track_inserted = HashSet::new();
+
+        let mut rng = thread_rng();
+        let distribution = Uniform::new_inclusive(0, 10u64.pow(12));
+        for _ in 0..10u32.pow(7) {
+            let value = rng.sample(distribution).to_be_bytes();
+            bf.insert(&value);
+            track_inserted.insert(value);
+        }
+
+        let mut false_positive = 0;
+        for _ in 0..10u32.pow(6) {
+            let value = rng.sample(distribution).to_be_bytes();
+            let found = bf.lookup(&value);
+            if found && !track_inserted.contains(&value) {
+                false_positive += 1;
+            }
+        }
+
+        dbg!("regular", false_positive);
+        // check that false positive rate is ~2%
+        assert!(19900 < false_positive && false_positive < 21000);
+    }
+
+    #[test]
+    fn insert_tracked_returns_set_bits() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let indices = bf.insert_tracked(&42u32.to_be_bytes());
+
+        assert!(!indices.is_empty() && indices.len() <= bf.k as usize);
+        let set_bits: Vec<usize> = (0..bf.get_size())
+            .filter(|&i| bf.storage.get(i) == Some(true))
+            .collect();
+        assert_eq!(indices, set_bits);
+        assert!(
+            bf.lookup(&42u32.to_be_bytes()),
+            "stored value is not found!"
+        );
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let mut source = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..900u32 {
+            source.insert(&i.to_be_bytes());
+        }
+        let mut target = source.clone();
+        for i in 900..910u32 {
+            target.insert(&i.to_be_bytes());
+        }
+
+        let diff = source.diff(&target).unwrap();
+        assert!(diff.len() <= 10 * target.k as usize);
+        assert!(diff.len() < target.get_size() / 100);
+
+        source.apply_diff(&diff).unwrap();
+        assert_eq!(source, target);
+    }
+
+    #[test]
+    fn diff_incompatible() {
+        let a = ClassicalBloomFilter::new(1000, 0.01);
+        let mut b = ClassicalBloomFilter::new(2000, 0.01);
+        assert_eq!(a.diff(&b), Err(FilterError::IncompatibleParameters));
+
+        let diff = a.diff(&a).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(
+            b.apply_diff(&diff),
+            Err(FilterError::IncompatibleParameters)
+        );
+    }
+
+    #[test]
+    fn diff_bytes_round_trip() {
+        let mut source = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..900u32 {
+            source.insert(&i.to_be_bytes());
+        }
+        let mut target = source.clone();
+        for i in 900..910u32 {
+            target.insert(&i.to_be_bytes());
+        }
+
+        // only the bytes travel, the receiver has just its own copy
+        let bytes = source.diff(&target).unwrap().to_bytes();
+        let diff = BloomDiff::from_bytes(&bytes).unwrap();
+        assert_eq!(diff, source.diff(&target).unwrap());
+        source.apply_diff(&diff).unwrap();
+        assert_eq!(source, target);
+
+        assert!(matches!(
+            BloomDiff::from_bytes(&bytes[..10]),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+        let mut past_end = source.diff(&source).unwrap().to_bytes();
+        write_varint(&mut past_end, source.m);
+        assert!(matches!(
+            BloomDiff::from_bytes(&past_end),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn lookup_mask_matches_lookup() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let values: Vec<[u8; 4]> = (0..200u32).map(|i| i.to_be_bytes()).collect();
+        for value in values.iter().step_by(2) {
+            bf.insert(value);
+        }
+
+        let queries: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+        let mask = bf.lookup_mask(&queries);
+        assert_eq!(mask.len(), queries.len());
+        for (i, query) in queries.iter().enumerate() {
+            assert_eq!(mask[i], bf.lookup(query));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn emits_construction_event() {
+        let events = captured_events(|| {
+            ClassicalBloomFilter::new(100, 0.01);
+        });
+        assert_eq!(events, ["created classical bloom filter"]);
+    }
+
+    #[test]
+    fn from_parts_check() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        bf.insert(&1u32.to_be_bytes());
+        bf.insert(&10u32.to_be_bytes());
+
+        let rebuilt = ClassicalBloomFilter::from_parts(bf.m, bf.k, bf.storage.clone()).unwrap();
+        assert_eq!(rebuilt, bf);
+        assert!(
+            rebuilt.lookup(&1u32.to_be_bytes()),
+            "stored value is not found!"
+        );
+        assert!(
+            rebuilt.lookup(&10u32.to_be_bytes()),
+            "stored value is not found!"
+        );
+    }
+
+    #[test]
+    fn from_blocks_and_bytes() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..100u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        // bits produced outside the crate, e.g. by bit-vec's own BitVec<u32>
+        let mut external = BitVec::from_elem(bf.m as usize, false);
+        for idx in bf.insert_tracked(&1000u32.to_be_bytes()) {
+            external.set(idx, true);
+        }
+        let mut rebuilt =
+            ClassicalBloomFilter::from_bytes(bf.m, bf.k, &external.to_bytes()).unwrap();
+        rebuilt.merge_into(&bf).unwrap();
+        assert_eq!(rebuilt, bf);
+        assert_eq!(
+            ClassicalBloomFilter::from_bytes(bf.m, bf.k, &bf.as_bytes()[1..]),
+            Err(FilterError::InvalidEncoding(
+                "number of bytes doesn't match m".into()
+            ))
+        );
+
+        let mut blocks = bf.as_bit_slice().to_vec();
+        *blocks.last_mut().unwrap() |= 1 << (Block::BITS - 1);
+        assert!(matches!(
+            ClassicalBloomFilter::from_blocks(bf.m, bf.k, &blocks),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+        assert!(matches!(
+            ClassicalBloomFilter::from_blocks(bf.m, bf.k, &blocks[1..]),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn from_parts_invalid() {
+        let res = ClassicalBloomFilter::from_parts(100, 3, BitVec::from_elem(64, false));
+        assert_eq!(
+            res,
+            Err(FilterError::LengthMismatch {
+                expected: 100,
+                actual: 64
+            })
+        );
+
+        let res = ClassicalBloomFilter::from_parts(64, 0, BitVec::from_elem(64, false));
+        assert_eq!(res, Err(FilterError::ZeroHashFunctions));
+
+        let res = ClassicalBloomFilter::from_parts(0, 3, BitVec::new());
+        assert_eq!(res, Err(FilterError::ZeroBits));
+
+        let res = ClassicalBloomFilter::from_parts(64, u64::MAX, BitVec::from_elem(64, false));
+        assert_eq!(res, Err(FilterError::TooManyHashFunctions(u64::MAX)));
+    }
+
+    #[test]
+    fn hamming_distance_check() {
+        let mut a = ClassicalBloomFilter::new(100, 0.01);
+        let mut b = a.clone();
+        assert_eq!(a.hamming_distance(&b), Ok(0));
+
+        a.storage.set(3, true);
+        a.storage.set(40, true);
+        b.storage.set(40, true);
+        b.storage.set(77, true);
+        b.storage.set(90, true);
+        assert_eq!(a.hamming_distance(&b), Ok(3));
+        assert_eq!(b.hamming_distance(&a), Ok(3));
+
+        let c = ClassicalBloomFilter::new(200, 0.01);
+        assert_eq!(
+            a.hamming_distance(&c),
+            Err(FilterError::IncompatibleParameters)
+        );
+    }
+
+    #[test]
+    fn insert_numeric_slices() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let wide: Vec<u64> = (0..50).map(|i| i * 1_000_003).collect();
+        let narrow: Vec<u32> = (0..50).map(|i| i * 7 + 1).collect();
+        bf.insert_u64_slice(&wide);
+        bf.insert_u32_slice(&narrow);
+
+        for value in &wide {
+            assert!(
+                bf.lookup(&value.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+        for value in &narrow {
+            assert!(
+                bf.lookup(&value.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+    }
+
+    #[test]
+    fn rebuild_if_needed_check() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let items: Vec<Vec<u8>> = (0..1000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        for item in &items {
+            bf.insert(item);
+        }
+        assert!(bf.current_false_positive_rate() > 0.05);
+
+        assert!(bf.rebuild_if_needed(|| items.clone(), 0.05));
+        assert!(bf.current_false_positive_rate() < 0.05);
+        let inserted: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        bf.assert_contains_inserted(&inserted);
+
+        assert!(!bf.rebuild_if_needed(|| items.clone(), 0.05));
+    }
+
+    #[test]
+    fn storage_checksum_check() {
+        let mut a = ClassicalBloomFilter::new(100, 0.01);
+        let mut b = ClassicalBloomFilter::new(100, 0.01);
+        for i in 0..50u32 {
+            a.insert(&i.to_be_bytes());
+            b.insert(&i.to_be_bytes());
+        }
+        assert_eq!(a.storage_checksum(), b.storage_checksum());
+
+        b.insert(&50u32.to_be_bytes());
+        assert_ne!(a.storage_checksum(), b.storage_checksum());
+    }
+
+    #[test]
+    fn union_many_check() {
+        let shards: Vec<ClassicalBloomFilter> = (0..4u32)
+            .map(|shard| {
+                let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+                for i in 0..100u32 {
+                    bf.insert(&(shard * 100 + i).to_be_bytes());
+                }
+                bf
+            })
+            .collect();
+
+        let union = ClassicalBloomFilter::union_many(&shards).unwrap();
+        for i in 0..400u32 {
+            assert!(union.lookup(&i.to_be_bytes()), "stored value is not found!");
+        }
+    }
+
+    #[test]
+    fn union_many_invalid() {
+        assert_eq!(
+            ClassicalBloomFilter::union_many(&[]),
+            Err(FilterError::EmptyInput)
+        );
+
+        let filters = [
+            ClassicalBloomFilter::new(1000, 0.01),
+            ClassicalBloomFilter::new(2000, 0.01),
+        ];
+        assert_eq!(
+            ClassicalBloomFilter::union_many(&filters),
+            Err(FilterError::IncompatibleParameters)
+        );
+    }
+
+    #[test]
+    fn with_algo_check() {
+        let algos = [
+            HashAlgo::Xxh3,
+            #[cfg(feature = "ahash")]
+            HashAlgo::AHash,
+            #[cfg(feature = "fnv")]
+            HashAlgo::Fnv,
+        ];
+        for algo in algos {
+            let mut bf = ClassicalBloomFilter::with_algo(1000, 0.01, algo);
+            for i in 0..1000u32 {
+                bf.insert(&i.to_be_bytes());
+            }
+            for i in 0..1000u32 {
+                assert!(bf.lookup(&i.to_be_bytes()), "stored value is not found!");
+            }
+            let false_positives = (1000..11000u32)
+                .filter(|i| bf.lookup(&i.to_be_bytes()))
+                .count();
+            assert!(
+                false_positives < 200,
+                "{algo:?}: {false_positives} false positives"
+            );
+        }
+        assert_eq!(
+            ClassicalBloomFilter::with_algo(1000, 0.01, HashAlgo::Xxh3),
+            ClassicalBloomFilter::new(1000, 0.01)
+        );
+    }
+
+    #[test]
+    fn hash_pairs_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        let pairs: Vec<(u64, u64)> = (0..1000u32)
+            .map(|i| base_hashes(&i.to_be_bytes()))
+            .collect();
+        bf.insert_hash_pairs(&pairs);
+
+        for (i, &(h1, h2)) in pairs.iter().enumerate() {
+            assert!(bf.lookup_hash_pair(h1, h2), "stored value is not found!");
+            assert!(
+                bf.lookup(&(i as u32).to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+        let (h1, h2) = base_hashes(&5000u32.to_be_bytes());
+        assert_eq!(
+            bf.lookup_hash_pair(h1, h2),
+            bf.lookup(&5000u32.to_be_bytes())
+        );
+    }
+
+    #[test]
+    fn from_bit_probabilities_check() {
+        let probs: Vec<f64> = (0..1000).map(|i| (i % 10) as f64 / 10.0).collect();
+        let bf = ClassicalBloomFilter::from_bit_probabilities(1000, 3, &probs, 0.7).unwrap();
+        for (idx, prob) in probs.iter().enumerate() {
+            assert_eq!(bf.storage[idx], *prob >= 0.7);
+        }
+        assert_eq!(bf.set_bits(), 300);
+
+        let res = ClassicalBloomFilter::from_bit_probabilities(10, 3, &probs, 0.5);
+        assert_eq!(
+            res,
+            Err(FilterError::LengthMismatch {
+                expected: 10,
+                actual: 1000
+            })
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_build_matches_sequential() {
+        use rayon::prelude::*;
+
+        let n = 10u32.pow(6);
+        let par = ClassicalBloomFilter::par_build(
+            n,
+            0.01,
+            (0..n).into_par_iter().map(|i| i.to_be_bytes()),
+        );
+        let mut seq = ClassicalBloomFilter::new(n, 0.01);
+        for i in 0..n {
+            seq.insert(&i.to_be_bytes());
+        }
+        assert_eq!(par, seq);
+
+        let empty = ClassicalBloomFilter::par_build(n, 0.01, rayon::iter::empty::<Vec<u8>>());
+        assert_eq!(empty, ClassicalBloomFilter::new(n, 0.01));
+
+        // far more splits than threads
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let par = pool.install(|| {
+            ClassicalBloomFilter::par_build(
+                n,
+                0.01,
+                (0..n)
+                    .into_par_iter()
+                    .with_max_len(64)
+                    .map(|i| i.to_be_bytes()),
+            )
+        });
+        assert_eq!(par, seq);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn arrow_round_trip() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let batch = bf.to_arrow().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.schema().as_ref(), &arrow_schema());
+        let decoded = ClassicalBloomFilter::from_arrow(&batch).unwrap();
+        assert_eq!(decoded, bf);
+        for i in 0..1000u32 {
+            assert!(
+                decoded.lookup(&i.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+
+        let projected = batch.project(&[0, 1, 3]).unwrap();
+        assert!(matches!(
+            ClassicalBloomFilter::from_arrow(&projected),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn overlap_ratio_check() {
+        let mut a = ClassicalBloomFilter::new(10000, 0.01);
+        let mut b = ClassicalBloomFilter::new(10000, 0.01);
+        assert_eq!(a.overlap_ratio(&b), Ok(0.0));
+        // a holds 0..4000, b holds 3000..8000, a quarter of a is in b
+        for i in 0..4000u32 {
+            a.insert(&i.to_be_bytes());
+        }
+        for i in 3000..8000u32 {
+            b.insert(&i.to_be_bytes());
+        }
+
+        let ratio = a.overlap_ratio(&b).unwrap();
+        assert!((ratio - 0.25).abs() < 0.03, "ratio {ratio}");
+        let ratio = b.overlap_ratio(&a).unwrap();
+        assert!((ratio - 0.2).abs() < 0.03, "ratio {ratio}");
+        assert!((a.overlap_ratio(&a).unwrap() - 1.0).abs() < 1e-9);
+
+        let res = a.overlap_ratio(&ClassicalBloomFilter::new(100, 0.01));
+        assert_eq!(res, Err(FilterError::IncompatibleParameters));
+    }
+
+    #[test]
+    fn union_dyn_check() {
+        let mut a = ClassicalBloomFilter::new(1000, 0.01);
+        let mut b = ClassicalBloomFilter::new(1000, 0.01);
+        a.insert(&1u32.to_be_bytes());
+        b.insert(&2u32.to_be_bytes());
+        let boxed: [Box<dyn FilterDyn>; 2] = [Box::new(a), Box::new(b)];
+
+        let union = union_dyn(&[boxed[0].as_ref(), boxed[1].as_ref()]).unwrap();
+        union.assert_contains_inserted(&[&1u32.to_be_bytes(), &2u32.to_be_bytes()]);
+        assert!(
+            !union.lookup(&3u32.to_be_bytes()),
+            "not stored value is found!"
+        );
+
+        let partitioned = crate::bloom_filters::PartitionedBloomFilter::new(1000, 0.01);
+        let res = union_dyn(&[boxed[0].as_ref(), &partitioned]);
+        assert_eq!(res, Err(FilterError::IncompatibleParameters));
+        let other = ClassicalBloomFilter::new(10, 0.01);
+        let res = union_dyn(&[boxed[0].as_ref(), &other]);
+        assert_eq!(res, Err(FilterError::IncompatibleParameters));
+        assert_eq!(union_dyn(&[]), Err(FilterError::EmptyInput));
+    }
+
+    #[test]
+    fn sparse_bytes_round_trip() {
+        let mut bf = ClassicalBloomFilter::new(10000, 0.01);
+        assert_eq!(
+            ClassicalBloomFilter::from_sparse_bytes(&bf.to_sparse_bytes().unwrap()),
+            Ok(bf.clone())
+        );
+        for i in 0..100u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let bytes = bf.to_sparse_bytes().unwrap();
+        assert!(
+            bytes.len() * 4 < bf.as_bytes().len(),
+            "{} bytes",
+            bytes.len()
+        );
+        let decoded = ClassicalBloomFilter::from_sparse_bytes(&bytes).unwrap();
+        assert_eq!(decoded, bf);
+
+        let mut truncated = bytes.clone();
+        truncated.push(0x80);
+        assert!(matches!(
+            ClassicalBloomFilter::from_sparse_bytes(&truncated),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+        let mut past_end = ClassicalBloomFilter::new(10, 0.01)
+            .to_sparse_bytes()
+            .unwrap();
+        past_end.push(100);
+        assert!(matches!(
+            ClassicalBloomFilter::from_sparse_bytes(&past_end),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+
+        let mut huge = u64::MAX.to_le_bytes().to_vec();
+        huge.extend_from_slice(&3u64.to_le_bytes());
+        huge.push(0);
+        assert!(matches!(
+            ClassicalBloomFilter::from_sparse_bytes(&huge),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+        let mut zero_bits = 0u64.to_le_bytes().to_vec();
+        zero_bits.extend_from_slice(&3u64.to_le_bytes());
+        zero_bits.push(0);
+        assert_eq!(
+            ClassicalBloomFilter::from_sparse_bytes(&zero_bits),
+            Err(FilterError::ZeroBits)
+        );
+    }
+
+    #[test]
+    fn probe_iter_check() {
+        let mut bf = ClassicalBloomFilter::with_hash_fns(1000, 0.01, |v| v.len() as u64, |_| 7);
+        let mut default = ClassicalBloomFilter::new(1000, 0.01);
+        for filter in [&mut bf, &mut default] {
+            for i in 0..100u32 {
+                let value = i.to_be_bytes();
+                let mut indices: Vec<usize> = filter.probe_iter(&value).collect();
+                assert_eq!(indices.len(), filter.k as usize);
+                indices.sort_unstable();
+                indices.dedup();
+                assert_eq!(filter.insert_tracked(&value), indices);
+            }
+            for i in 0..1000u32 {
+                let value = i.to_be_bytes();
+                let found = filter.probe_iter(&value).all(|idx| filter.storage[idx]);
+                assert_eq!(found, filter.lookup(&value));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(any(feature = "ahash", feature = "fnv")),
+        ignore = "needs a second hash algorithm, the ahash or fnv feature"
+    )]
+    fn rehash_check() {
+        let algos = [
+            HashAlgo::Xxh3,
+            #[cfg(feature = "ahash")]
+            HashAlgo::AHash,
+            #[cfg(feature = "fnv")]
+            HashAlgo::Fnv,
+        ];
+        assert!(algos.len() >= 2, "nothing to migrate between");
+        let items: Vec<Vec<u8>> = (0..1000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let inserted: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        for old_algo in algos {
+            for new_algo in algos.into_iter().filter(|&algo| algo != old_algo) {
+                let mut old = ClassicalBloomFilter::with_algo(1000, 0.01, old_algo);
+                items.iter().for_each(|item| old.insert(item));
+
+                let migrated =
+                    ClassicalBloomFilter::rehash(old_algo, new_algo, 1000, 0.01, items.clone());
+                assert_eq!(migrated.hashing, Hashing::Algo(new_algo));
+                assert_ne!(migrated.hashing, old.hashing);
+                assert_ne!(migrated.storage, old.storage);
+                migrated.assert_contains_inserted(&inserted);
+            }
+        }
+    }
+
+    #[test]
+    fn region_counts_check() {
+        // values starting with 0 all probe the first few bits
+        let mut bf = ClassicalBloomFilter::with_hash_fns(
+            10000,
+            0.01,
+            |v| match v[0] {
+                0 => v[1] as u64,
+                _ => xxh3_64_with_seed(v, 0),
+            },
+            |v| match v[0] {
+                0 => 1,
+                _ => xxh3_64_with_seed(v, 64),
+            },
+        );
+        bf.insert(&[0, 0]);
+        assert_eq!(bf.region_counts(), [0; REGIONS]);
+
+        bf.enable_region_counts();
+        for i in 0..2000u32 {
+            let mut value = i.to_be_bytes();
+            value[0] = (i % 2) as u8;
+            bf.insert(&value);
+        }
+        let counts = bf.region_counts();
+        assert_eq!(counts.iter().sum::<u64>(), 2000 * bf.k);
+        assert!(counts[0] >= 1000 * bf.k);
+        assert!(counts[0] > 10 * counts[1..].iter().max().unwrap());
+
+        bf.reset_with(1000, 0.01);
+        assert_eq!(bf.region_counts(), [0; REGIONS]);
+    }
+
+    #[test]
+    fn as_bit_slice_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let blocks = bf.as_bit_slice();
+        let bits = Block::BITS as usize;
+        assert_eq!(blocks.len(), (bf.num_bits() as usize).div_ceil(bits));
+        let rebuilt =
+            ClassicalBloomFilter::from_blocks(bf.num_bits(), bf.num_hashes(), blocks).unwrap();
+        assert_eq!(rebuilt, bf);
+        for i in 0..5000u32 {
+            assert_eq!(
+                rebuilt.lookup(&i.to_be_bytes()),
+                bf.lookup(&i.to_be_bytes())
+            );
+        }
+    }
+
+    #[test]
+    fn fold_bytes_check() {
+        let items: Vec<Vec<u8>> = (0..3000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let chunks = items.chunks(1000).map(|shard_items| {
+            let mut shard = ClassicalBloomFilter::new(3000, 0.01);
+            shard_items.iter().for_each(|item| shard.insert(item));
+            let mut bytes = Vec::new();
+            shard.write_compact(&mut bytes).unwrap();
+            bytes
+        });
+
+        let mut acc = ClassicalBloomFilter::new(3000, 0.01);
+        acc.fold_bytes(chunks).unwrap();
+        let inserted: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        acc.assert_contains_inserted(&inserted);
+
+        let mut other = Vec::new();
+        ClassicalBloomFilter::new(10, 0.01)
+            .write_compact(&mut other)
+            .unwrap();
+        let res = acc.fold_bytes([other.clone()].into_iter());
+        assert_eq!(res, Err(FilterError::IncompatibleParameters));
+        let res = acc.fold_bytes([other[..4].to_vec()].into_iter());
+        assert!(matches!(res, Err(FilterError::InvalidEncoding(_))));
+    }
+
+    #[test]
+    fn custom_bit_store() {
+        struct VecStore(Vec<bool>);
+
+        impl BitStore for VecStore {
+            fn get(&self, i: usize) -> bool {
+                self.0[i]
+            }
+            fn set(&mut self, i: usize, v: bool) {
+                self.0[i] = v;
+            }
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+            fn count_ones(&self) -> u64 {
+                self.0.iter().filter(|&&bit| bit).count() as u64
+            }
+        }
+
+        let mut reference = ClassicalBloomFilter::new(100, 0.01);
+        let store = VecStore(vec![false; reference.m as usize]);
+        let mut bf = ClassicalBloomFilter::from_parts(reference.m, reference.k, store).unwrap();
+        for i in 0..100u32 {
+            bf.insert(&i.to_be_bytes());
+            reference.insert(&i.to_be_bytes());
+        }
+
+        for i in 0..100u32 {
+            assert!(bf.lookup(&i.to_be_bytes()), "stored value is not found!");
+        }
+        for i in 100..1000u32 {
+            assert_eq!(
+                bf.lookup(&i.to_be_bytes()),
+                reference.lookup(&i.to_be_bytes())
+            );
+        }
+        assert_eq!(bf.storage.count_ones(), reference.set_bits());
+    }
+
+    #[test]
+    fn entropy_coded_round_trip() {
+        let mut bf = ClassicalBloomFilter::new(10000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let mut bytes = Vec::new();
+        bf.write_entropy_coded(&mut bytes).unwrap();
+        assert!(bytes.len() < bf.get_size() / 8 / 2);
+
+        let decoded = ClassicalBloomFilter::read_entropy_coded(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, bf);
+    }
+
+    #[test]
+    fn entropy_coded_corrupt_header() {
+        let header = |m: u64, len: u64| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&m.to_le_bytes());
+            bytes.extend_from_slice(&3u64.to_le_bytes());
+            bytes.push(0);
+            bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+            bytes
+        };
+        let res = ClassicalBloomFilter::read_entropy_coded(&mut header(64, u64::MAX).as_slice());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let res = ClassicalBloomFilter::read_entropy_coded(&mut header(u64::MAX, 0).as_slice());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn entropy_coded_empty_and_full() {
+        let empty = ClassicalBloomFilter::new(1000, 0.01);
+        let mut full = empty.clone();
+        full.storage.set_all();
+
+        for bf in [empty, full] {
+            let mut bytes = Vec::new();
+            bf.write_entropy_coded(&mut bytes).unwrap();
+            let decoded = ClassicalBloomFilter::read_entropy_coded(&mut bytes.as_slice()).unwrap();
+            assert_eq!(decoded, bf);
+        }
+    }
+
+    #[test]
+    fn build_shards_check() {
+        let items: Vec<Vec<u8>> = (0..4000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let shards = ClassicalBloomFilter::build_shards(4, 1500, 0.01, items.clone());
+        assert_eq!(shards.len(), 4);
+
+        let mut foreign_hits = 0;
+        for item in &items {
+            let target = ClassicalBloomFilter::shard_of(item, 4);
+            for (i, shard) in shards.iter().enumerate() {
+                if i == target {
+                    assert!(shard.lookup(item), "stored value is not found!");
+                } else if shard.lookup(item) {
+                    foreign_hits += 1;
+                }
+            }
+        }
+        // 3 foreign lookups per item at ~1% fpr
+        assert!(foreign_hits < 3 * 4000 * 2 / 100);
+    }
+
+    #[test]
+    fn effective_k_check() {
+        let bf = ClassicalBloomFilter::new(1000, 0.01);
+        let values: Vec<[u8; 4]> = (0..10000u32).map(|i| i.to_be_bytes()).collect();
+        let sample: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+
+        let effective_k = bf.effective_k_sample(&sample);
+        assert!(effective_k <= bf.k as f64);
+        assert!(effective_k > bf.k as f64 - 0.1);
+    }
+
+    #[test]
+    fn try_insert_check() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let mut inserted = Vec::new();
+        for i in 0..1000u32 {
+            match bf.try_insert(&i.to_be_bytes(), 0.5) {
+                Ok(()) => inserted.push(i),
+                Err(e) => {
+                    assert_eq!(e, FilterError::Full);
+                    break;
+                }
+            }
+        }
+        assert!(bf.fill_ratio() >= 0.5);
+        assert!(inserted.len() < 1000);
+
+        assert_eq!(
+            bf.try_insert(&5000u32.to_be_bytes(), 0.5),
+            Err(FilterError::Full)
+        );
+        for i in &inserted {
+            assert!(bf.lookup(&i.to_be_bytes()), "stored value is not found!");
+        }
+    }
+
+    #[test]
+    fn stats_check() {
+        let mut bf = ClassicalBloomFilter::new(10000, 0.01);
+        let empty = bf.stats();
+        assert_eq!(empty.set_bits, 0);
+        assert_eq!(empty.estimated_count, 0);
+
+        for i in 0..5000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let stats = bf.stats();
+        assert_eq!(stats.m, bf.get_size() as u64);
+        assert_eq!(stats.k, bf.k);
+        assert_eq!(stats.fill_ratio, stats.set_bits as f64 / stats.m as f64);
+        assert_eq!(stats.current_fpr, stats.fill_ratio.powi(stats.k as i32));
+        assert!(stats.set_bits <= 5000 * stats.k);
+        assert!(stats.estimated_count.abs_diff(5000) < 100);
+    }
+
+    #[test]
+    fn hash_distribution_is_flat() {
+        let bf = ClassicalBloomFilter::new(10000, 0.01);
+        let mut rng = thread_rng();
+        let values: Vec<[u8; 8]> = (0..10000).map(|_| rng.gen::<u64>().to_be_bytes()).collect();
+        let sample: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+
+        let histogram = bf.hash_distribution(&sample);
+        assert_eq!(histogram.len(), HASH_DISTRIBUTION_BUCKETS);
+        let total: u64 = histogram.iter().sum();
+        assert_eq!(total, 10000 * bf.k);
+
+        let expected = total as f64 / HASH_DISTRIBUTION_BUCKETS as f64;
+        let chi_square: f64 = histogram
+            .iter()
+            .map(|&observed| (observed as f64 - expected).powi(2) / expected)
+            .sum();
+        // 15 degrees of freedom, p = 0.001 critical value is ~37.7
+        assert!(chi_square < 37.7, "chi-square {chi_square} is too high");
+    }
+
+    #[test]
+    fn from_set_check() {
+        let set: HashSet<Vec<u8>> = (0..1000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let bf = ClassicalBloomFilter::from_set(&set, 0.01);
+        assert_eq!(bf.m, ClassicalBloomFilter::calculate_m(0.01, 1000));
+        let inserted: Vec<&[u8]> = set.iter().map(Vec::as_slice).collect();
+        bf.assert_contains_inserted(&inserted);
+
+        let empty = ClassicalBloomFilter::from_set(&HashSet::new(), 0.01);
+        assert!(!empty.lookup(b"anything"), "not stored value is found!");
+    }
+
+    #[test]
+    fn should_rebuild_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        assert!(!bf.should_rebuild(0.01));
+
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert!(!bf.should_rebuild(0.02));
+
+        for i in 1000..3000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert!(bf.should_rebuild(0.02));
+        assert!(bf.rebuild_recommended_capacity() as u64 > bf.estimate_count());
+    }
+
+    #[test]
+    fn reset_with_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let buffer = bf.storage.storage().as_ptr();
+        bf.reset_with(500, 0.01);
+        assert_eq!(bf.storage.storage().as_ptr(), buffer);
+        assert_eq!(bf.m, ClassicalBloomFilter::calculate_m(0.01, 500));
+        assert_eq!(bf.k, ClassicalBloomFilter::calculate_k(bf.m, 500));
+        assert_eq!(bf.get_size(), bf.m as usize);
+        assert_eq!(bf.set_bits(), 0);
+
+        bf.reset_with(100000, 0.001);
+        assert_ne!(bf.storage.storage().as_ptr(), buffer);
+        assert_eq!(bf.m, ClassicalBloomFilter::calculate_m(0.001, 100000));
+        assert_eq!(bf.get_size(), bf.m as usize);
+        assert_eq!(bf.set_bits(), 0);
+        assert!(
+            !bf.lookup(&1u32.to_be_bytes()),
+            "not stored value is found!"
+        );
+    }
+
+    #[test]
+    fn probe_match_count_check() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        for i in 0..1000u32 {
+            assert_eq!(bf.probe_match_count(&i.to_be_bytes()), bf.k as u32);
+        }
+
+        let below_k = (1000..2000u32)
+            .filter(|i| bf.probe_match_count(&i.to_be_bytes()) < bf.k as u32)
+            .count();
+        assert!(below_k > 950);
+    }
+
+    #[test]
+    fn block_sizes_agree() {
+        let mut narrow = ClassicalBloomFilter::<BitVec<u32>>::new_with_blocks(1000, 0.01);
+        let mut wide = ClassicalBloomFilter::<BitVec<u64>>::new_with_blocks(1000, 0.01);
+        for i in 0..1000u32 {
+            narrow.insert(&i.to_be_bytes());
+            wide.insert(&i.to_be_bytes());
+        }
+
+        for i in 0..5000u32 {
+            assert_eq!(
+                narrow.lookup(&i.to_be_bytes()),
+                wide.lookup(&i.to_be_bytes())
+            );
+        }
+        assert_eq!(narrow.as_bytes(), wide.as_bytes());
+        assert_eq!(narrow.as_bytes().len(), (narrow.m as usize).div_ceil(8));
+        assert_eq!(narrow.memory_bytes(), (narrow.m as usize).div_ceil(32) * 4);
+        assert_eq!(wide.memory_bytes(), (wide.m as usize).div_ceil(64) * 8);
+    }
+
+    #[test]
+    fn memory_layout_check() {
+        let mut bf = ClassicalBloomFilter::<BitVec<u32>>::new_with_blocks(1000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let layout = bf.memory_layout();
+        assert_eq!(layout.word_bytes, 4);
+        assert_eq!(layout.total_bytes, bf.memory_bytes());
+        assert_eq!(
+            layout.num_words,
+            bf.as_bytes().len().div_ceil(layout.word_bytes)
+        );
+        assert_eq!(layout.bit_order, BitOrder::Lsb0);
+
+        let words = bf.storage.storage();
+        let bits = layout.word_bytes * 8;
+        for idx in 0..bf.m as usize {
+            let bit = (words[idx / bits] >> (idx % bits)) & 1 == 1;
+            assert_eq!(bit, bf.storage[idx]);
+        }
+    }
+
+    #[test]
+    fn expected_false_positives_check() {
+        let mut bf = ClassicalBloomFilter::new(10000, 0.05);
+        for i in 0..10000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+
+        let queries = 100000u32;
+        let observed = (10000..10000 + queries)
+            .filter(|i| bf.lookup(&i.to_be_bytes()))
+            .count() as f64;
+        let expected = bf.expected_false_positives(queries as u64);
+        // binomial standard deviation is ~sqrt(expected)
+        assert!(
+            (observed - expected).abs() < 5.0 * expected.sqrt(),
+            "observed {observed}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn merge_resizing_check() {
+        let small_items: Vec<Vec<u8>> = (0..100u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let large_items: Vec<Vec<u8>> = (100..5100u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let mut small = ClassicalBloomFilter::new(100, 0.01);
+        let mut large = ClassicalBloomFilter::new(5000, 0.02);
+        small_items.iter().for_each(|item| small.insert(item));
+        large_items.iter().for_each(|item| large.insert(item));
+
+        let merged = ClassicalBloomFilter::merge_resizing(
+            &[&small, &large],
+            vec![small_items.clone(), large_items.clone()],
+            0.01,
+        )
+        .unwrap();
+        assert!(merged.m >= ClassicalBloomFilter::calculate_m(0.01, 5100));
+        let inserted: Vec<&[u8]> = small_items
+            .iter()
+            .chain(&large_items)
+            .map(Vec::as_slice)
+            .collect();
+        merged.assert_contains_inserted(&inserted);
+
+        let res = ClassicalBloomFilter::merge_resizing(&[&small, &large], vec![small_items], 0.01);
+        assert_eq!(res, Err(FilterError::MissingItemSource { filter: 1 }));
+    }
+
+    #[test]
+    fn merge_resizing_saturated() {
+        let items: Vec<Vec<u8>> = (0..1000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let mut saturated = ClassicalBloomFilter::new(10, 0.01);
+        items.iter().for_each(|item| saturated.insert(item));
+        saturated.storage.set_all();
+        assert_eq!(saturated.estimate_count(), u64::MAX);
+        let mut other = ClassicalBloomFilter::new(1000, 0.01);
+        other.insert(&1000u32.to_be_bytes());
+
+        let merged = ClassicalBloomFilter::merge_resizing(
+            &[&saturated, &other],
+            vec![items.clone(), vec![1000u32.to_be_bytes().to_vec()]],
+            0.01,
+        )
+        .unwrap();
+        assert_eq!(merged.m, ClassicalBloomFilter::calculate_m(0.01, 1001));
+        let inserted: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        merged.assert_contains_inserted(&inserted);
+    }
+
+    #[test]
+    fn combine_compatible() {
+        let mut a = ClassicalBloomFilter::new(1000, 0.01);
+        let mut b = ClassicalBloomFilter::new(1000, 0.01);
+        a.insert(&1u32.to_be_bytes());
+        b.insert(&2u32.to_be_bytes());
+
+        let combined =
+            ClassicalBloomFilter::combine(&a, &b, || panic!("items requested on the OR path"))
+                .unwrap();
+        let mut expected = a.clone();
+        expected.merge_into(&b).unwrap();
+        assert_eq!(combined, expected);
+    }
+
+    #[test]
+    fn combine_incompatible() {
+        let items: Vec<Vec<u8>> = (0..3000u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let mut a = ClassicalBloomFilter::new(1000, 0.01);
+        let mut b = ClassicalBloomFilter::new(2000, 0.01);
+        items[..1000].iter().for_each(|item| a.insert(item));
+        items[1000..].iter().for_each(|item| b.insert(item));
+
+        let combined = ClassicalBloomFilter::combine(&a, &b, || items.clone()).unwrap();
+        assert!(combined.m >= ClassicalBloomFilter::calculate_m(0.01, 3000));
+        let inserted: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+        combined.assert_contains_inserted(&inserted);
+
+        let res = ClassicalBloomFilter::combine(&a, &b, Vec::new);
+        assert_eq!(res, Err(FilterError::EmptyInput));
+
+        // a saturated filter is the typical one to rebuild
+        a.storage.set_all();
+        assert_eq!(a.estimate_count(), u64::MAX);
+        let combined = ClassicalBloomFilter::combine(&a, &b, || items.clone()).unwrap();
+        assert!(combined.m >= ClassicalBloomFilter::calculate_m(0.01, 3000));
+        combined.assert_contains_inserted(&inserted);
+    }
+
+    #[test]
+    fn density_map_check() {
+        let mut bf = ClassicalBloomFilter::new(10000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        // cluster of set bits in the 4th of 16 buckets
+        let bucket_size = bf.get_size() / 16;
+        for idx in 3 * bucket_size..3 * bucket_size + bucket_size / 2 {
+            bf.storage.set(idx, true);
+        }
+
+        let map = bf.density_map(16);
+        assert_eq!(map.len(), 16);
+        assert!(map[3] >= 0.5);
+        for (bucket, density) in map.iter().enumerate() {
+            if bucket != 3 {
+                assert!(*density < 0.2, "bucket {bucket} is too dense");
+            }
+        }
+        assert!(bf.density_map(0).is_empty());
+    }
+
+    #[test]
+    fn with_hash_fns_check() {
+        fn fnv1a(value: &[u8]) -> u64 {
+            value.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+        }
+        fn fnv1a_rev(value: &[u8]) -> u64 {
+            value.iter().rev().fold(0xcbf29ce484222325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+        }
+
+        let mut custom = ClassicalBloomFilter::with_hash_fns(1000, 0.01, fnv1a, fnv1a_rev);
+        let mut default = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            custom.insert(&i.to_be_bytes());
+            default.insert(&i.to_be_bytes());
+        }
+        for i in 0..1000u32 {
+            assert!(
+                custom.lookup(&i.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+        assert_ne!(custom.storage, default.storage);
+        assert_eq!(
+            custom.merge_into(&default),
+            Err(FilterError::IncompatibleParameters)
+        );
+        // the functions can't be identified, not even the same ones
+        let same = ClassicalBloomFilter::with_hash_fns(1000, 0.01, fnv1a, fnv1a_rev);
+        assert_ne!(same, same.clone());
+        assert_eq!(
+            custom.clone().merge_into(&same),
+            Err(FilterError::IncompatibleParameters)
+        );
+
+        assert_eq!(custom.to_sparse_bytes(), Err(FilterError::CustomHashFns));
+        let res = custom.write_compact(&mut Vec::new());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let res = custom.write_entropy_coded(&mut Vec::new());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        #[cfg(feature = "arrow")]
+        assert_eq!(custom.to_arrow().err(), Some(FilterError::CustomHashFns));
+    }
+
+    #[test]
+    fn serialized_algo_round_trip() {
+        let algos = [
+            HashAlgo::Xxh3,
+            #[cfg(feature = "ahash")]
+            HashAlgo::AHash,
+            #[cfg(feature = "fnv")]
+            HashAlgo::Fnv,
+        ];
+        for algo in algos {
+            let mut bf = ClassicalBloomFilter::with_algo(1000, 0.01, algo);
+            for i in 0..1000u32 {
+                bf.insert(&i.to_be_bytes());
+            }
+
+            let mut compact = Vec::new();
+            bf.write_compact(&mut compact).unwrap();
+            let mut entropy = Vec::new();
+            bf.write_entropy_coded(&mut entropy).unwrap();
+            let decoded = [
+                ClassicalBloomFilter::read_compact(&mut compact.as_slice()).unwrap(),
+                ClassicalBloomFilter::read_entropy_coded(&mut entropy.as_slice()).unwrap(),
+                ClassicalBloomFilter::from_sparse_bytes(&bf.to_sparse_bytes().unwrap()).unwrap(),
+            ];
+            #[cfg(feature = "arrow")]
+            let decoded = decoded
+                .into_iter()
+                .chain([ClassicalBloomFilter::from_arrow(&bf.to_arrow().unwrap()).unwrap()]);
+            for filter in decoded {
+                assert_eq!(filter, bf);
+                for i in 0..1000u32 {
+                    assert!(
+                        filter.lookup(&i.to_be_bytes()),
+                        "stored value is not found!"
+                    );
+                }
+            }
+        }
+
+        let mut unknown = ClassicalBloomFilter::new(10, 0.01)
+            .to_sparse_bytes()
+            .unwrap();
+        unknown[16] = 200;
+        assert!(matches!(
+            ClassicalBloomFilter::from_sparse_bytes(&unknown),
+            Err(FilterError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let mut bf = ClassicalBloomFilter::new(10u32.pow(6), 0.01);
+        let mut bytes = Vec::new();
+        bf.write_compact(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 25);
+        let decoded = ClassicalBloomFilter::read_compact(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, bf);
+
+        for i in 0..100u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let mut bytes = Vec::new();
+        bf.write_compact(&mut bytes).unwrap();
+        assert!(bytes.len() <= 25 + bf.as_bytes().len());
+        let decoded = ClassicalBloomFilter::read_compact(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, bf);
+    }
+
+    #[test]
+    fn compact_corrupt_header() {
+        let header = |m: u64, k: u64, algo: u8, used: u64| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&m.to_le_bytes());
+            bytes.extend_from_slice(&k.to_le_bytes());
+            bytes.push(algo);
+            bytes.extend_from_slice(&used.to_le_bytes());
+            bytes
+        };
+        let res = ClassicalBloomFilter::read_compact(&mut header(u64::MAX, 3, 0, 0).as_slice());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let res =
+            ClassicalBloomFilter::read_compact(&mut header(u64::MAX, 3, 0, 1 << 40).as_slice());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let res = ClassicalBloomFilter::read_compact(&mut header(64, u64::MAX, 0, 0).as_slice());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let res = ClassicalBloomFilter::read_compact(&mut header(64, 3, 200, 0).as_slice());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidData);
     }
 }
@@ -0,0 +1,124 @@
+//! Binary range coder (LZMA style) with a static probability model,
+//! used to compress a bit array whose fill ratio is known.
+
+/// probabilities are expressed as p / 2^PROB_BITS
+pub(crate) const PROB_BITS: u32 = 16;
+const TOP: u32 = 1 << 24;
+
+/// probability of a 0 bit for the given fill ratio,
+/// clamped so that both symbols stay encodable
+pub(crate) fn zero_probability(fill_ratio: f64) -> u16 {
+    ((1.0 - fill_ratio) * (1u32 << PROB_BITS) as f64)
+        .round()
+        .clamp(1.0, u16::MAX as f64) as u16
+}
+
+pub(crate) struct Encoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    prob_zero: u32,
+    out: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn new(prob_zero: u16) -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            prob_zero: prob_zero as u32,
+            out: Vec::new(),
+        }
+    }
+
+    pub(crate) fn encode(&mut self, bit: bool) {
+        let bound = (self.range >> PROB_BITS) * self.prob_zero;
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+        } else {
+            self.range = bound;
+        }
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        self.out
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xFF00_0000 || self.low > u32::MAX as u64 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            loop {
+                self.out.push(byte.wrapping_add(carry));
+                byte = 0xFF;
+                self.cache_size -= 1;
+                if self.cache_size == 0 {
+                    break;
+                }
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+}
+
+pub(crate) struct Decoder<'a> {
+    code: u32,
+    range: u32,
+    prob_zero: u32,
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// input must have been produced by an `Encoder` with the same probability,
+    /// reading past its end yields zero bytes
+    pub(crate) fn new(prob_zero: u16, input: &'a [u8]) -> Self {
+        let mut decoder = Self {
+            code: 0,
+            range: u32::MAX,
+            prob_zero: prob_zero as u32,
+            input,
+            pos: 0,
+        };
+        for _ in 0..5 {
+            decoder.code = (decoder.code << 8) | decoder.next_byte() as u32;
+        }
+        decoder
+    }
+
+    pub(crate) fn decode(&mut self) -> bool {
+        let bound = (self.range >> PROB_BITS) * self.prob_zero;
+        let bit = if self.code < bound {
+            self.range = bound;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            true
+        };
+        while self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.next_byte() as u32;
+        }
+        bit
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.input.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        byte
+    }
+}
@@ -0,0 +1,77 @@
+use crate::bloom_filters::MAX_HASH_FUNCTIONS;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterError {
+    /// filters (or a filter and a derived structure) were built with
+    /// different m, k or hash algorithm, or with custom hash functions,
+    /// and can't be combined
+    IncompatibleParameters,
+    /// bit storage length doesn't match the number of bits m
+    LengthMismatch { expected: usize, actual: usize },
+    /// a filter needs at least one hash function
+    ZeroHashFunctions,
+    /// a filter needs at least one bit
+    ZeroBits,
+    /// more hash functions than `MAX_HASH_FUNCTIONS`, a corrupt header
+    TooManyHashFunctions(u64),
+    /// operation needs at least one filter
+    EmptyInput,
+    /// filter reached its fill cap and refuses further inserts
+    Full,
+    /// no item source was given for the filter at this position
+    MissingItemSource { filter: usize },
+    /// filter spec names a type that isn't known
+    UnknownFilterType(String),
+    /// filter spec parameters are missing or can't be parsed
+    InvalidSpec(String),
+    /// serialized filter couldn't be decoded
+    InvalidEncoding(String),
+    /// filter uses custom hash functions, which can't be recorded in a
+    /// serialized header
+    CustomHashFns,
+    /// rounding of m and k makes the filter miss its target false positive
+    /// rate, sizing it for suggested_n elements would meet it
+    CannotMeetTarget { suggested_n: u32 },
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::IncompatibleParameters => {
+                write!(f, "filters have different number of bits or hash functions")
+            }
+            FilterError::LengthMismatch { expected, actual } => {
+                write!(f, "expected storage of {expected} bits, got {actual}")
+            }
+            FilterError::ZeroHashFunctions => write!(f, "number of hash functions must be >= 1"),
+            FilterError::ZeroBits => write!(f, "number of bits must be >= 1"),
+            FilterError::TooManyHashFunctions(k) => write!(
+                f,
+                "{k} hash functions, at most {MAX_HASH_FUNCTIONS} are supported"
+            ),
+            FilterError::EmptyInput => write!(f, "no filters were given"),
+            FilterError::Full => write!(f, "filter is full"),
+            FilterError::MissingItemSource { filter } => {
+                write!(
+                    f,
+                    "no item source for filter #{filter}, its bits can't be rehashed"
+                )
+            }
+            FilterError::UnknownFilterType(name) => write!(f, "unknown filter type {name:?}"),
+            FilterError::InvalidSpec(reason) => write!(f, "invalid filter spec: {reason}"),
+            FilterError::InvalidEncoding(reason) => {
+                write!(f, "can't decode serialized filter: {reason}")
+            }
+            FilterError::CustomHashFns => {
+                write!(f, "filters with custom hash functions can't be serialized")
+            }
+            FilterError::CannotMeetTarget { suggested_n } => write!(
+                f,
+                "target false positive rate can't be met, size the filter for {suggested_n} elements"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
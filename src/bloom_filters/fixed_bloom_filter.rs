@@ -0,0 +1,76 @@
+use crate::bloom_filters::DoubleHashing;
+
+/// Classical Bloom filter stored inline in a `[u64; WORDS]`, so it never
+/// allocates and can live on the stack or in a `static`.
+///
+/// The size is fixed at compile time: the filter has `BITS = 64 * WORDS`
+/// bits (stable Rust can't derive the array length from a bit count), e.g.
+/// `FixedBloomFilter<16>` is a 1024 bit filter. k is chosen at construction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedBloomFilter<const WORDS: usize> {
+    /// number of hash functions
+    k: u64,
+
+    words: [u64; WORDS],
+}
+
+impl<const WORDS: usize> FixedBloomFilter<WORDS> {
+    /// number of bits in the filter
+    pub const BITS: usize = WORDS * 64;
+
+    /// k -- number of hash functions
+    pub const fn new(k: u64) -> Self {
+        assert!(WORDS > 0, "filter needs at least one word");
+        assert!(k > 0, "number of hash functions must be >= 1");
+        Self {
+            k,
+            words: [0; WORDS],
+        }
+    }
+
+    pub fn insert(&mut self, value: &[u8]) {
+        for idx in Self::probes(value, self.k, Self::BITS as u64) {
+            self.words[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    pub fn lookup(&self, value: &[u8]) -> bool {
+        Self::probes(value, self.k, Self::BITS as u64)
+            .all(|idx| self.words[idx / 64] & (1 << (idx % 64)) != 0)
+    }
+
+    pub fn get_size(&self) -> usize {
+        Self::BITS
+    }
+}
+
+impl<const WORDS: usize> DoubleHashing for FixedBloomFilter<WORDS> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_simple_check() {
+        let mut bf = FixedBloomFilter::<16>::new(7);
+        assert_eq!(bf.get_size(), 1024);
+        assert_eq!(std::mem::size_of_val(&bf), 1024 / 8 + 8);
+
+        for i in 0..100u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        for i in 0..100u32 {
+            assert!(bf.lookup(&i.to_be_bytes()), "stored value is not found!");
+        }
+        assert!(
+            !bf.lookup(&1000u32.to_be_bytes()),
+            "not stored value is found!"
+        );
+    }
+
+    #[test]
+    fn fixed_in_static() {
+        static EMPTY: FixedBloomFilter<4> = FixedBloomFilter::new(3);
+        assert!(!EMPTY.lookup(b"anything"), "not stored value is found!");
+    }
+}
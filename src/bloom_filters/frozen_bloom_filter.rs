@@ -0,0 +1,91 @@
+use crate::bloom_filters::{Block, DoubleHashing, Hashing, Probes};
+
+/// Read-only form of a `ClassicalBloomFilter`, see
+/// `ClassicalBloomFilter::freeze`. Nothing can be inserted anymore: the
+/// bits are a boxed slice of exactly ceil(m / block bits) blocks without
+/// spare capacity, and there is only lookup and serialization.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrozenBloomFilter {
+    m: u64,
+    k: u64,
+    /// bit i is bit `i % Block::BITS` (least significant first) of block
+    /// `i / Block::BITS`
+    blocks: Box<[Block]>,
+    hashing: Hashing,
+}
+
+impl FrozenBloomFilter {
+    pub(crate) fn from_blocks(m: u64, k: u64, blocks: Box<[Block]>, hashing: Hashing) -> Self {
+        Self {
+            m,
+            k,
+            blocks,
+            hashing,
+        }
+    }
+
+    fn value_probes(&self, value: &[u8]) -> Probes {
+        match self.hashing.hash_fns() {
+            Some((h1, h2)) => Probes::from_hashes(h1(value), h2(value), self.k, self.m),
+            None => Self::probes(value, self.k, self.m),
+        }
+    }
+
+    pub fn lookup(&self, value: &[u8]) -> bool {
+        self.value_probes(value).all(|idx| {
+            let block = self.blocks[idx / Block::BITS as usize];
+            block >> (idx % Block::BITS as usize) & 1 == 1
+        })
+    }
+
+    /// number of bits m
+    pub fn get_size(&self) -> usize {
+        self.m as usize
+    }
+
+    pub fn num_hashes(&self) -> u64 {
+        self.k
+    }
+
+    /// same layout as `ClassicalBloomFilter::as_bytes`
+    pub fn as_bytes(&self) -> Vec<u8> {
+        (0..self.m as usize)
+            .step_by(8)
+            .map(|start| {
+                (start..(start + 8).min(self.m as usize)).fold(0u8, |byte, idx| {
+                    let bit =
+                        self.blocks[idx / Block::BITS as usize] >> (idx % Block::BITS as usize) & 1;
+                    byte | (bit as u8) << (7 - (idx - start))
+                })
+            })
+            .collect()
+    }
+}
+
+impl DoubleHashing for FrozenBloomFilter {}
+
+#[cfg(test)]
+mod tests {
+    use crate::bloom_filters::{ClassicalBloomFilter, Filter};
+
+    #[test]
+    fn freeze_keeps_memberships() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let frozen = bf.clone().freeze();
+
+        assert_eq!(frozen.get_size(), bf.get_size());
+        assert_eq!(frozen.as_bytes(), bf.as_bytes());
+        for i in 0..1000u32 {
+            assert!(
+                frozen.lookup(&i.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+        for i in 1000..20000u32 {
+            assert_eq!(frozen.lookup(&i.to_be_bytes()), bf.lookup(&i.to_be_bytes()));
+        }
+    }
+}
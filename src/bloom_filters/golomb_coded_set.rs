@@ -0,0 +1,155 @@
+use bit_vec::BitVec;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// every INDEX_INTERVAL-th element gets an entry in the sparse index
+const INDEX_INTERVAL: usize = 64;
+
+/// Static membership set storing the sorted hashes of its items in
+/// [0, n * P) as Golomb-Rice coded differences, where P = ceil(1/f).
+///
+/// It takes about log2(1/f) + 2 bits per item, less than the
+/// 1.44 * log2(1/f) bits of a Bloom filter for small f, but it can't be
+/// modified after `build` and a lookup has to decode the gaps. The sparse
+/// index limits a lookup to a binary search plus decoding at most
+/// INDEX_INTERVAL gaps, at the cost of 16 bytes per INDEX_INTERVAL items.
+pub struct GolombCodedSet {
+    /// hashes are reduced to [0, range)
+    range: u64,
+    /// number of remainder bits of the Rice code
+    rice_bits: u32,
+    /// number of encoded (distinct) hashes
+    len: usize,
+    bits: BitVec,
+    /// value of every INDEX_INTERVAL-th element and the bit offset
+    /// right after its code
+    index: Vec<(u64, usize)>,
+}
+
+impl GolombCodedSet {
+    /// items -- elements of the set
+    /// f -- the false positive rate
+    pub fn build(items: &[&[u8]], f: f64) -> Self {
+        let p = (1.0 / f).ceil().max(1.0) as u64;
+        let range = items.len() as u64 * p;
+        let rice_bits = p.ilog2();
+
+        let mut hashes: Vec<u64> = items
+            .iter()
+            .map(|item| Self::reduce(xxh3_64(item), range))
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let mut bits = BitVec::new();
+        let mut index = Vec::with_capacity(hashes.len() / INDEX_INTERVAL + 1);
+        let mut previous = 0;
+        for (i, &hash) in hashes.iter().enumerate() {
+            let delta = hash - previous;
+            for _ in 0..delta >> rice_bits {
+                bits.push(true);
+            }
+            bits.push(false);
+            for bit in (0..rice_bits).rev() {
+                bits.push((delta >> bit) & 1 == 1);
+            }
+            if i % INDEX_INTERVAL == 0 {
+                index.push((hash, bits.len()));
+            }
+            previous = hash;
+        }
+
+        Self {
+            range,
+            rice_bits,
+            len: hashes.len(),
+            bits,
+            index,
+        }
+    }
+
+    pub fn lookup(&self, value: &[u8]) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+        let target = Self::reduce(xxh3_64(value), self.range);
+        let entry = self.index.partition_point(|&(hash, _)| hash <= target);
+        if entry == 0 {
+            return false;
+        }
+        let (mut current, mut offset) = self.index[entry - 1];
+        let remaining = (self.len - (entry - 1) * INDEX_INTERVAL - 1).min(INDEX_INTERVAL - 1);
+        for _ in 0..remaining {
+            if current >= target {
+                break;
+            }
+            let mut quotient = 0;
+            while self.bits[offset] {
+                quotient += 1;
+                offset += 1;
+            }
+            offset += 1;
+            let mut remainder = 0;
+            for _ in 0..self.rice_bits {
+                remainder = (remainder << 1) | self.bits[offset] as u64;
+                offset += 1;
+            }
+            current += (quotient << self.rice_bits) | remainder;
+        }
+        current == target
+    }
+
+    /// size of the encoded hashes in bits, without the index
+    pub fn get_size(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// maps hash uniformly into [0, range)
+    fn reduce(hash: u64, range: u64) -> u64 {
+        ((hash as u128 * range as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn gcs_no_false_negatives() {
+        let values: Vec<[u8; 4]> = (0..10000u32).map(|i| i.to_be_bytes()).collect();
+        let items: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+        let gcs = GolombCodedSet::build(&items, 0.01);
+
+        for item in &items {
+            assert!(gcs.lookup(item), "stored value is not found!");
+        }
+        // ~log2(1/f) + 2 bits per item
+        assert!(gcs.get_size() < items.len() * 10);
+    }
+
+    #[test]
+    fn gcs_false_positive_rate() {
+        let mut rng = thread_rng();
+        let inserted: HashSet<u64> = (0..10000).map(|_| rng.gen()).collect();
+        let values: Vec<[u8; 8]> = inserted.iter().map(|v| v.to_be_bytes()).collect();
+        let items: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+        let gcs = GolombCodedSet::build(&items, 0.01);
+
+        let mut false_positive = 0;
+        for _ in 0..100000 {
+            let value: u64 = rng.gen();
+            if gcs.lookup(&value.to_be_bytes()) && !inserted.contains(&value) {
+                false_positive += 1;
+            }
+        }
+        // check that false positive rate is ~1%
+        assert!(800 < false_positive && false_positive < 1200);
+    }
+
+    #[test]
+    fn gcs_empty() {
+        let gcs = GolombCodedSet::build(&[], 0.01);
+        assert!(!gcs.lookup(b"anything"), "not stored value is found!");
+    }
+}
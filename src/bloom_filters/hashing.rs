@@ -0,0 +1,299 @@
+use crate::bloom_filters::HashFn;
+use xxhash_rust::const_xxh3::const_custom_default_secret;
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_secret, xxh3_64_with_seed};
+
+/// seed of the first base hash used by the crate's filters
+pub const SEED1: u64 = 0;
+/// seed of the second base hash used by the crate's filters
+pub const SEED2: u64 = 64;
+
+/// The two unreduced base hashes the crate's filters derive their probes
+/// from, `Probes::from_hashes(h1, h2, k, m)` yields the same indices as
+/// the filters' insert and lookup.
+pub fn base_hashes(value: &[u8]) -> (u64, u64) {
+    // a seeded xxh3 derives a 192-byte secret from the seed on every call
+    // for inputs longer than 240 bytes, for those hashing with the
+    // precomputed secret gives the same result
+    let hash2 = if value.len() > MID_SIZE_MAX {
+        xxh3_64_with_secret(value, &SEED2_SECRET)
+    } else {
+        xxh3_64_with_seed(value, SEED2)
+    };
+    (xxh3_64(value), hash2)
+}
+
+/// inputs up to this length are hashed by xxh3 without a derived secret
+const MID_SIZE_MAX: usize = 240;
+const SEED2_SECRET: [u8; 192] = const_custom_default_secret(SEED2);
+const _: () = assert!(SEED1 == 0, "base_hashes hashes unseeded for SEED1");
+
+/// Base hash algorithm of `ClassicalBloomFilter::with_algo`, the extra
+/// algorithms are behind the features of the same name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgo {
+    /// seeded xxh3, what all filters use by default
+    Xxh3,
+    /// aHash with fixed keys, its output isn't stable across ahash
+    /// versions or target platforms
+    #[cfg(feature = "ahash")]
+    AHash,
+    /// 64-bit FNV-1a with two offset bases, fast on tiny keys but of
+    /// lower quality than the others
+    #[cfg(feature = "fnv")]
+    Fnv,
+}
+
+/// id of `HashAlgo::Xxh3` in serialized headers
+const XXH3_ID: u8 = 0;
+/// id of `HashAlgo::AHash`, reserved even without the feature
+const AHASH_ID: u8 = 1;
+/// id of `HashAlgo::Fnv`, reserved even without the feature
+const FNV_ID: u8 = 2;
+
+impl HashAlgo {
+    /// the two base hash functions, None for the built-in xxh3
+    pub(crate) fn hash_fns(self) -> Option<(HashFn, HashFn)> {
+        match self {
+            HashAlgo::Xxh3 => None,
+            #[cfg(feature = "ahash")]
+            HashAlgo::AHash => Some((ahash1, ahash2)),
+            #[cfg(feature = "fnv")]
+            HashAlgo::Fnv => Some((fnv1, fnv2)),
+        }
+    }
+
+    /// id recorded in the header of serialized filters
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            HashAlgo::Xxh3 => XXH3_ID,
+            #[cfg(feature = "ahash")]
+            HashAlgo::AHash => AHASH_ID,
+            #[cfg(feature = "fnv")]
+            HashAlgo::Fnv => FNV_ID,
+        }
+    }
+
+    /// the algorithm of a header id, errors for unknown ids and for
+    /// algorithms whose feature isn't enabled
+    pub(crate) fn from_id(id: u8) -> Result<Self, String> {
+        match id {
+            XXH3_ID => Ok(HashAlgo::Xxh3),
+            #[cfg(feature = "ahash")]
+            AHASH_ID => Ok(HashAlgo::AHash),
+            #[cfg(not(feature = "ahash"))]
+            AHASH_ID => Err("filter was hashed with aHash, enable the ahash feature".into()),
+            #[cfg(feature = "fnv")]
+            FNV_ID => Ok(HashAlgo::Fnv),
+            #[cfg(not(feature = "fnv"))]
+            FNV_ID => Err("filter was hashed with FNV, enable the fnv feature".into()),
+            _ => Err(format!("unknown hash algorithm {id}")),
+        }
+    }
+}
+
+/// Base hashes of a filter: a built-in algorithm, which serializers
+/// record, or caller-supplied functions, which can't be identified.
+/// Only filters with the same built-in algorithm are equal or compatible.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Hashing {
+    Algo(HashAlgo),
+    Custom(HashFn, HashFn),
+}
+
+impl Hashing {
+    /// the two base hash functions, None for the built-in xxh3
+    pub(crate) fn hash_fns(self) -> Option<(HashFn, HashFn)> {
+        match self {
+            Hashing::Algo(algo) => algo.hash_fns(),
+            Hashing::Custom(h1, h2) => Some((h1, h2)),
+        }
+    }
+
+    /// the algorithm, None for custom hash functions
+    pub(crate) fn algo(self) -> Option<HashAlgo> {
+        match self {
+            Hashing::Algo(algo) => Some(algo),
+            Hashing::Custom(..) => None,
+        }
+    }
+}
+
+/// function pointers aren't reliably comparable, so custom hash functions
+/// are never equal, not even to themselves
+impl PartialEq for Hashing {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.algo(), other.algo()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "ahash")]
+fn ahash1(value: &[u8]) -> u64 {
+    ahash::RandomState::with_seeds(SEED1, 1, 2, 3).hash_one(value)
+}
+
+#[cfg(feature = "ahash")]
+fn ahash2(value: &[u8]) -> u64 {
+    ahash::RandomState::with_seeds(SEED2, 1, 2, 3).hash_one(value)
+}
+
+#[cfg(feature = "fnv")]
+fn fnv1(value: &[u8]) -> u64 {
+    use std::hash::Hasher;
+    let mut hasher = fnv::FnvHasher::default();
+    hasher.write(value);
+    hasher.finish()
+}
+
+#[cfg(feature = "fnv")]
+fn fnv2(value: &[u8]) -> u64 {
+    use std::hash::Hasher;
+    // offset basis of FNV-1a with a different starting point
+    let mut hasher = fnv::FnvHasher::with_key(0x84222325cbf29ce4);
+    hasher.write(value);
+    hasher.finish()
+}
+
+/// Double hashing shared by the filters: two xxh3 hashes with different
+/// seeds are reduced mod m and the i-th probe is `(h1 + i * h2) % m`.
+/// Implementations can override the seeds.
+pub trait DoubleHashing {
+    const SEED1: u64 = SEED1;
+    const SEED2: u64 = SEED2;
+
+    /// the two unreduced base hashes of value under `SEED1` and `SEED2`
+    fn hashes(value: &[u8]) -> (u64, u64) {
+        if Self::SEED1 == SEED1 && Self::SEED2 == SEED2 {
+            return base_hashes(value);
+        }
+        (
+            xxh3_64_with_seed(value, Self::SEED1),
+            xxh3_64_with_seed(value, Self::SEED2),
+        )
+    }
+
+    /// the k probe indices of value in [0, m)
+    fn probes(value: &[u8], k: u64, m: u64) -> Probes {
+        let (hash1, hash2) = Self::hashes(value);
+        Probes::from_hashes(hash1, hash2, k, m)
+    }
+}
+
+/// Iterator over the probe indices of a value, see `DoubleHashing::probes`
+#[derive(Clone, Debug)]
+pub struct Probes {
+    hash1: u64,
+    hash2: u64,
+    i: u64,
+    k: u64,
+    m: u64,
+}
+
+impl Probes {
+    /// the k probe indices in [0, m) derived from two base hashes
+    pub fn from_hashes(hash1: u64, hash2: u64, k: u64, m: u64) -> Self {
+        Self {
+            hash1: hash1 % m,
+            hash2: hash2 % m,
+            i: 0,
+            k,
+            m,
+        }
+    }
+}
+
+impl Iterator for Probes {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.i == self.k {
+            return None;
+        }
+        let idx = ((self.hash1 + self.i * self.hash2) % self.m) as usize;
+        self.i += 1;
+        Some(idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.k - self.i) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Probes {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom_filters::{ClassicalBloomFilter, Filter, PartitionedBloomFilter};
+
+    #[test]
+    fn shared_probes() {
+        let mut bf = PartitionedBloomFilter::new(1000, 0.01);
+        let k = bf.num_hashes();
+        let partition_size = bf.num_bits() / k;
+        for i in 0..100u32 {
+            let value = i.to_be_bytes();
+            let mut classical: Vec<usize> =
+                <ClassicalBloomFilter>::probes(&value, k, partition_size).collect();
+            classical.sort_unstable();
+
+            // one probe per partition, at the shared index within it
+            let inserted = bf.insert_tracked(&value);
+            let mut partitions: Vec<u64> = inserted
+                .iter()
+                .map(|&idx| idx as u64 / partition_size)
+                .collect();
+            partitions.dedup();
+            assert_eq!(partitions.len() as u64, k);
+            let mut partitioned: Vec<usize> = inserted
+                .iter()
+                .map(|&idx| (idx as u64 % partition_size) as usize)
+                .collect();
+            partitioned.sort_unstable();
+            assert_eq!(classical, partitioned);
+        }
+    }
+
+    #[test]
+    fn probes_match_inserted_bits() {
+        let mut bf = ClassicalBloomFilter::new(100, 0.01);
+        let value = 42u32.to_be_bytes();
+        let mut expected: Vec<usize> =
+            <ClassicalBloomFilter>::probes(&value, bf.num_hashes(), bf.num_bits()).collect();
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(bf.insert_tracked(&value), expected);
+    }
+
+    #[test]
+    fn base_hashes_match_filters() {
+        let mut bf = ClassicalBloomFilter::new(1000, 0.01);
+        for i in 0..100u32 {
+            let value = i.to_be_bytes();
+            let (h1, h2) = base_hashes(&value);
+            let mut expected: Vec<usize> =
+                Probes::from_hashes(h1, h2, bf.num_hashes(), bf.num_bits()).collect();
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(bf.insert_tracked(&value), expected);
+        }
+    }
+
+    #[test]
+    fn base_hashes_match_seeded_xxh3() {
+        for len in [0, 1, 4, 16, 17, 128, 129, 240, 241, 1024, 5000] {
+            let value: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+            assert_eq!(
+                base_hashes(&value),
+                (
+                    xxh3_64_with_seed(&value, SEED1),
+                    xxh3_64_with_seed(&value, SEED2)
+                ),
+                "length {len}"
+            );
+        }
+    }
+}
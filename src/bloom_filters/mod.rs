@@ -1,7 +1,42 @@
+mod auditor;
 mod base;
+mod bit_store;
 mod classical_bloom_filter;
+mod entropy_coding;
+mod error;
+mod fixed_bloom_filter;
+mod frozen_bloom_filter;
+mod golomb_coded_set;
+mod hashing;
 mod partitioned_bloom_filter;
+mod prefix_bloom_filter;
+mod resizable_bloom_filter;
+#[cfg(feature = "roaring")]
+mod sparse_bloom_filter;
+mod spec;
+mod timed;
 
-pub use self::base::Filter;
-pub use self::classical_bloom_filter::ClassicalBloomFilter;
+pub use self::auditor::FalsePositiveAuditor;
+pub use self::base::{
+    bits_for_target, compare_to_exact, min_k_for_fpr, presence_count, Filter, FilterDyn,
+    FilterStats, FilterWarning, MemoryComparison, PARTITION_IMBALANCE_LIMIT, REBUILD_HEADROOM,
+    SATURATION_FILL, STRICT_FPR_MARGIN,
+};
+pub use self::bit_store::{BitOrder, BitStore, Block, MemoryLayout};
+pub use self::classical_bloom_filter::{
+    union_dyn, BloomDiff, ClassicalBloomFilter, HashFn, HASH_DISTRIBUTION_BUCKETS,
+    MAX_HASH_FUNCTIONS,
+};
+pub use self::error::FilterError;
+pub use self::fixed_bloom_filter::FixedBloomFilter;
+pub use self::frozen_bloom_filter::FrozenBloomFilter;
+pub use self::golomb_coded_set::GolombCodedSet;
+pub(crate) use self::hashing::Hashing;
+pub use self::hashing::{base_hashes, DoubleHashing, HashAlgo, Probes, SEED1, SEED2};
 pub use self::partitioned_bloom_filter::PartitionedBloomFilter;
+pub use self::prefix_bloom_filter::PrefixBloomFilter;
+pub use self::resizable_bloom_filter::ResizableBloomFilter;
+#[cfg(feature = "roaring")]
+pub use self::sparse_bloom_filter::SparseBloomFilter;
+pub use self::spec::from_spec;
+pub use self::timed::TimedFilter;
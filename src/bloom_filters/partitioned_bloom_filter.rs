@@ -1,6 +1,8 @@
-use crate::bloom_filters::base::Filter;
+use crate::bloom_filters::base::{
+    count_ones, health_warnings, Filter, FilterWarning, PARTITION_IMBALANCE_LIMIT,
+};
+use crate::bloom_filters::{DoubleHashing, Probes};
 use bit_vec::BitVec;
-use xxhash_rust::xxh3::xxh3_64_with_seed;
 
 pub struct PartitionedBloomFilter {
     /// number of hash functions
@@ -10,46 +12,134 @@ pub struct PartitionedBloomFilter {
     partitions: Vec<BitVec>,
 }
 
-impl Filter for PartitionedBloomFilter {
+impl PartitionedBloomFilter {
     /// n -- number of elements to insert
     /// f -- the false positive rate
-    fn new(n: u32, f: f64) -> Self {
+    /// partitions -- number of partitions m is distributed across
+    ///
+    /// k is still derived from n and f, every value probes k consecutive
+    /// partitions (wrapping around) starting at one picked by its hash,
+    /// so all partitions fill evenly whatever their number
+    pub fn with_partitions(n: u32, f: f64, partitions: usize) -> Self {
+        assert!(partitions >= 1, "at least one partition is required");
         let m = Self::calculate_m(f, n);
         let k = Self::calculate_k(m, n);
-        let partition_size = (m / k) as usize;
+        // round up so the partitions cover all m bits, and never go below
+        // one bit per partition to avoid modulo by zero on tiny filters
+        let partition_size = m.div_ceil(partitions as u64).max(1) as usize;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            n,
+            f,
+            m,
+            k,
+            partitions,
+            partition_size,
+            "created partitioned bloom filter"
+        );
         Self {
             k,
             partition_size,
-            partitions: std::iter::repeat(BitVec::from_elem(partition_size, false))
-                .take(k as usize)
+            partitions: std::iter::repeat_n(BitVec::from_elem(partition_size, false), partitions)
                 .collect(),
         }
     }
 
+    /// fraction of set bits in every partition
+    pub fn partition_fill_ratios(&self) -> Vec<f64> {
+        self.partitions
+            .iter()
+            .map(|p| count_ones(p) as f64 / self.partition_size as f64)
+            .collect()
+    }
+
+    /// Coefficient of variation (std dev / mean) of the partition fill
+    /// ratios, ~0 when the hashes spread evenly. 0 for an empty filter.
+    pub fn partition_imbalance(&self) -> f64 {
+        let ratios = self.partition_fill_ratios();
+        let mean = ratios.iter().sum::<f64>() / ratios.len() as f64;
+        if mean == 0.0 {
+            return 0.0;
+        }
+        let variance = ratios.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / ratios.len() as f64;
+        variance.sqrt() / mean
+    }
+
+    /// (partition, index in partition) of every probe of value
+    fn partition_probes(&self, value: &[u8]) -> impl Iterator<Item = (usize, usize)> + use<> {
+        let (hash1, hash2) = Self::hashes(value);
+        let size = self.partition_size as u64;
+        let partitions = self.partitions.len() as u64;
+        let k = self.k;
+        // the index in the partition uses hash1 % size, the high part is
+        // left for picking the first partition
+        let first = hash1 / size % partitions;
+        Probes::from_hashes(hash1, hash2, k, size)
+            .enumerate()
+            .map(move |(i, idx)| (((first + i as u64) % partitions) as usize, idx))
+    }
+}
+
+impl DoubleHashing for PartitionedBloomFilter {}
+
+impl Filter for PartitionedBloomFilter {
+    /// n -- number of elements to insert
+    /// f -- the false positive rate
+    fn new(n: u32, f: f64) -> Self {
+        let m = Self::calculate_m(f, n);
+        let k = Self::calculate_k(m, n);
+        Self::with_partitions(n, f, k.max(1) as usize)
+    }
+
     fn insert(&mut self, value: &[u8]) {
-        let hash1 = xxh3_64_with_seed(value, 0) % self.partition_size as u64;
-        let hash2 = xxh3_64_with_seed(value, 64) % self.partition_size as u64;
-        for i in 0..self.k {
-            let idx = ((hash1 + i * hash2) % self.partition_size as u64) as usize;
-            self.partitions[i as usize].set(idx, true);
+        for (p, idx) in self.partition_probes(value) {
+            self.partitions[p].set(idx, true);
         }
     }
 
-    fn lookup(&self, value: &[u8]) -> bool {
-        let hash1 = xxh3_64_with_seed(value, 0) % self.partition_size as u64;
-        let hash2 = xxh3_64_with_seed(value, 64) % self.partition_size as u64;
-        for i in 0..self.k {
-            let idx = ((hash1 + i * hash2) % self.partition_size as u64) as usize;
-            if self.partitions[i as usize].get(idx) == Some(false) {
-                return false;
-            }
+    /// returned indices are global: the bit `idx` of partition `p` is
+    /// reported as `p * partition_size + idx`
+    fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(self.k as usize);
+        for (p, idx) in self.partition_probes(value) {
+            self.partitions[p].set(idx, true);
+            indices.push(p * self.partition_size + idx);
         }
-        true
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    fn lookup(&self, value: &[u8]) -> bool {
+        self.partition_probes(value)
+            .all(|(p, idx)| self.partitions[p].get(idx) == Some(true))
     }
 
     fn get_size(&self) -> usize {
         self.partitions.len()
     }
+
+    fn num_bits(&self) -> u64 {
+        (self.partition_size * self.partitions.len()) as u64
+    }
+
+    fn num_hashes(&self) -> u64 {
+        self.k
+    }
+
+    fn set_bits(&self) -> u64 {
+        self.partitions.iter().map(count_ones).sum()
+    }
+
+    /// also flags `ImbalancedPartitions` above `PARTITION_IMBALANCE_LIMIT`
+    fn health_check(&self) -> Vec<FilterWarning> {
+        let mut warnings = health_warnings(self);
+        let imbalance = self.partition_imbalance();
+        if imbalance > PARTITION_IMBALANCE_LIMIT {
+            warnings.push(FilterWarning::ImbalancedPartitions { imbalance });
+        }
+        warnings
+    }
 }
 
 #[cfg(test)]
@@ -96,7 +186,7 @@ mod tests {
         for _ in 0..10u32.pow(6) {
             let value = rng.sample(distribution).to_be_bytes();
             let found = bf.lookup(&value);
-            if found && track_inserted.get(&value).is_none() {
+            if found && !track_inserted.contains(&value) {
                 false_positive += 1;
             }
         }
@@ -105,4 +195,141 @@ mod tests {
         // check that false positive rate is ~2%
         assert!(19900 < false_positive && false_positive < 21000);
     }
+
+    #[test]
+    fn partitioned_insert_tracked_returns_set_bits() {
+        let mut bf = PartitionedBloomFilter::new(100, 0.01);
+        let indices = bf.insert_tracked(&42u32.to_be_bytes());
+
+        assert_eq!(indices.len(), bf.k as usize);
+        let set_bits: Vec<usize> = bf
+            .partitions
+            .iter()
+            .enumerate()
+            .flat_map(|(i, p)| {
+                (0..p.len())
+                    .filter(|&idx| p.get(idx) == Some(true))
+                    .map(move |idx| i * bf.partition_size + idx)
+            })
+            .collect();
+        assert_eq!(indices, set_bits);
+    }
+
+    #[test]
+    fn with_partitions_check() {
+        // k is 7, also check fewer and more partitions than that
+        for partitions in [1, 4, 7, 16, 32] {
+            let mut bf = PartitionedBloomFilter::with_partitions(10000, 0.01, partitions);
+            assert_eq!(bf.get_size(), partitions);
+
+            let values: Vec<[u8; 4]> = (0..10000u32).map(|i| i.to_be_bytes()).collect();
+            let inserted: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+            for value in &inserted {
+                bf.insert(value);
+            }
+            bf.assert_contains_inserted(&inserted);
+
+            let false_positives = (10000..110000u32)
+                .filter(|i| bf.lookup(&i.to_be_bytes()))
+                .count();
+            let observed = false_positives as f64 / 100000.0;
+            assert!(
+                (observed - 0.01).abs() < 0.002,
+                "{partitions} partitions: observed fpr {observed}"
+            );
+            assert!((bf.current_false_positive_rate() - 0.01).abs() < 0.002);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn with_zero_partitions() {
+        PartitionedBloomFilter::with_partitions(1000, 0.01, 0);
+    }
+
+    #[test]
+    fn tiny_partitioned_filter() {
+        let mut bf = PartitionedBloomFilter::with_partitions(1, 0.5, 8);
+        bf.insert(&1u32.to_be_bytes());
+        assert!(bf.lookup(&1u32.to_be_bytes()), "stored value is not found!");
+
+        let mut bf = PartitionedBloomFilter::new(1, 1.0);
+        bf.insert(&1u32.to_be_bytes());
+        assert!(bf.lookup(&1u32.to_be_bytes()), "stored value is not found!");
+    }
+
+    #[test]
+    fn partitions_cover_m() {
+        for (n, f) in [(10, 0.01), (1000, 0.02), (12345, 0.001)] {
+            let m = PartitionedBloomFilter::calculate_m(f, n);
+            let bf = PartitionedBloomFilter::new(n, f);
+            let total: usize = bf.partitions.iter().map(|p| p.len()).sum();
+            assert!(total as u64 >= m);
+        }
+    }
+
+    #[test]
+    fn partitioned_fill_accessors() {
+        let mut bf = PartitionedBloomFilter::new(1000, 0.01);
+        assert_eq!(bf.set_bits(), 0);
+        assert_eq!(bf.current_false_positive_rate(), 0.0);
+
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert!(bf.set_bits() <= 1000 * bf.num_hashes());
+        assert!((bf.fill_ratio() - 0.5).abs() < 0.05);
+        assert!((bf.current_false_positive_rate() - 0.01).abs() < 0.005);
+    }
+
+    #[test]
+    fn partitioned_insert_all_estimated() {
+        let mut bf = PartitionedBloomFilter::new(10000, 0.01);
+        let values: Vec<[u8; 4]> = (0..5000u32).map(|i| i.to_be_bytes()).collect();
+        let batch: Vec<&[u8]> = values.iter().map(|v| v.as_slice()).collect();
+
+        let estimate = bf.insert_all_estimated(&batch);
+        assert!(estimate.abs_diff(5000) < 150, "estimate {estimate} is off");
+        assert_eq!(bf.insert_all_estimated(&batch), estimate);
+    }
+
+    #[test]
+    fn partition_balance_check() {
+        let mut bf = PartitionedBloomFilter::new(100000, 0.01);
+        assert_eq!(bf.partition_imbalance(), 0.0);
+
+        let mut rng = thread_rng();
+        for _ in 0..100000 {
+            bf.insert(&rng.gen::<u64>().to_be_bytes());
+        }
+        let ratios = bf.partition_fill_ratios();
+        assert_eq!(ratios.len(), bf.num_hashes() as usize);
+        assert!(ratios.iter().all(|r| (r - 0.5).abs() < 0.05));
+        assert!(
+            bf.partition_imbalance() < 0.01,
+            "{}",
+            bf.partition_imbalance()
+        );
+    }
+
+    #[test]
+    fn partitioned_health_check() {
+        let mut bf = PartitionedBloomFilter::new(1000, 0.01);
+        for i in 0..900u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        assert_eq!(bf.health_check(), vec![]);
+
+        // fill half of the first partition, leave the others empty
+        let mut bf = PartitionedBloomFilter::new(1000, 0.01);
+        for idx in 0..bf.partition_size / 2 {
+            bf.partitions[0].set(idx, true);
+        }
+        let warnings = bf.health_check();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(matches!(
+            warnings[0],
+            FilterWarning::ImbalancedPartitions { .. }
+        ));
+    }
 }
@@ -0,0 +1,77 @@
+use crate::bloom_filters::{ClassicalBloomFilter, Filter};
+
+/// Answers "is any key with this prefix present" by keeping one classical
+/// filter per prefix length and inserting every prefix of a key.
+///
+/// Space cost: `max_len` filters each sized for n elements at rate f, i.e.
+/// roughly `max_len * m` bits, and every insert costs up to `max_len * k`
+/// bit writes.
+pub struct PrefixBloomFilter {
+    /// levels[i] holds prefixes of length i + 1
+    levels: Vec<ClassicalBloomFilter>,
+}
+
+impl PrefixBloomFilter {
+    /// n -- number of keys to insert
+    /// f -- the false positive rate of each prefix length
+    /// max_len -- the longest prefix that can be queried
+    pub fn new(n: u32, f: f64, max_len: usize) -> Self {
+        Self {
+            levels: (0..max_len)
+                .map(|_| ClassicalBloomFilter::new(n, f))
+                .collect(),
+        }
+    }
+
+    /// inserts every prefix of key up to max_len bytes (capped
+    /// by the max_len the filter was built with)
+    pub fn insert_with_prefixes(&mut self, key: &[u8], max_len: usize) {
+        let max_len = max_len.min(key.len()).min(self.levels.len());
+        for (len, level) in self.levels.iter_mut().enumerate().take(max_len) {
+            level.insert(&key[..=len]);
+        }
+    }
+
+    /// the empty prefix is considered to match everything
+    pub fn contains_prefix(&self, prefix: &[u8]) -> bool {
+        match prefix.len() {
+            0 => true,
+            len if len > self.levels.len() => false,
+            len => self.levels[len - 1].lookup(prefix),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_check() {
+        let mut bf = PrefixBloomFilter::new(100, 0.01, 16);
+        bf.insert_with_prefixes(b"10.0.1.0/24", 16);
+
+        for len in 1..=b"10.0.1.0/24".len() {
+            assert!(
+                bf.contains_prefix(&b"10.0.1.0/24"[..len]),
+                "stored prefix is not found!"
+            );
+        }
+        assert!(
+            !bf.contains_prefix(b"192.168"),
+            "not stored prefix is found!"
+        );
+    }
+
+    #[test]
+    fn prefix_longer_than_max_len() {
+        let mut bf = PrefixBloomFilter::new(100, 0.01, 4);
+        bf.insert_with_prefixes(b"abcdef", 8);
+
+        assert!(bf.contains_prefix(b"abcd"), "stored prefix is not found!");
+        assert!(
+            !bf.contains_prefix(b"abcde"),
+            "prefix over max_len is found!"
+        );
+    }
+}
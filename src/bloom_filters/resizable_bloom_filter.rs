@@ -0,0 +1,116 @@
+use crate::bloom_filters::{ClassicalBloomFilter, Filter};
+
+/// Resizes without the item set: `grow` archives the current filter and
+/// starts a new one for twice as many elements. Inserts go to the newest
+/// generation, lookups check all of them.
+///
+/// Every generation adds its false positives, after g grows the rate is
+/// up to (g + 1) * f, and a lookup costs up to (g + 1) * k probes.
+pub struct ResizableBloomFilter {
+    f: f64,
+    /// capacity of the newest generation
+    n: u32,
+    /// oldest first, the last one takes the inserts
+    generations: Vec<ClassicalBloomFilter>,
+}
+
+impl ResizableBloomFilter {
+    /// n -- number of elements the first generation is sized for
+    /// f -- the false positive rate of each generation
+    pub fn new(n: u32, f: f64) -> Self {
+        Self {
+            f,
+            n,
+            generations: vec![ClassicalBloomFilter::new(n, f)],
+        }
+    }
+
+    /// archives the current generation and starts an empty one sized
+    /// for twice as many elements
+    pub fn grow(&mut self) {
+        self.n = self.n.saturating_mul(2);
+        self.generations
+            .push(ClassicalBloomFilter::new(self.n, self.f));
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            generations = self.generations.len(),
+            n = self.n,
+            f = self.f,
+            "grew resizable bloom filter"
+        );
+    }
+
+    pub fn insert(&mut self, value: &[u8]) {
+        self.generations
+            .last_mut()
+            .expect("there is always a generation")
+            .insert(value);
+    }
+
+    pub fn lookup(&self, value: &[u8]) -> bool {
+        // newest first, recent items are usually the ones looked up
+        self.generations
+            .iter()
+            .rev()
+            .any(|generation| generation.lookup(value))
+    }
+
+    /// number of generations, 1 + number of grows
+    pub fn generations(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// summed bits of all generations
+    pub fn num_bits(&self) -> u64 {
+        self.generations.iter().map(Filter::num_bits).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_keeps_inserted() {
+        let mut bf = ResizableBloomFilter::new(1000, 0.01);
+        let values: Vec<[u8; 4]> = (0..7000u32).map(|i| i.to_be_bytes()).collect();
+
+        values[..1000].iter().for_each(|v| bf.insert(v));
+        bf.grow();
+        values[1000..3000].iter().for_each(|v| bf.insert(v));
+        bf.grow();
+        values[3000..].iter().for_each(|v| bf.insert(v));
+
+        assert_eq!(bf.generations(), 3);
+        assert_eq!(
+            bf.num_bits(),
+            [1000, 2000, 4000]
+                .iter()
+                .map(|&n| ClassicalBloomFilter::calculate_m(0.01, n))
+                .sum::<u64>()
+        );
+        for value in &values {
+            assert!(bf.lookup(value), "stored value is not found!");
+        }
+        let false_positives = (7000..17000u32)
+            .filter(|i| bf.lookup(&i.to_be_bytes()))
+            .count();
+        assert!(false_positives < 500, "{false_positives} false positives");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn emits_growth_event() {
+        use crate::bloom_filters::classical_bloom_filter::captured_events;
+
+        let mut bf = ResizableBloomFilter::new(1000, 0.01);
+        let events = captured_events(|| bf.grow());
+        assert_eq!(
+            events,
+            [
+                "created classical bloom filter",
+                "grew resizable bloom filter"
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::bloom_filters::bit_store::zeroed;
+use crate::bloom_filters::{ClassicalBloomFilter, DoubleHashing, Filter};
+use roaring::RoaringTreemap;
+
+/// Classical Bloom filter that keeps only the positions of set bits in a
+/// roaring bitmap, for huge filters that stay sparsely populated.
+///
+/// Roaring stores up to 4096 set bits of each 2^16 bit chunk as 2 byte
+/// entries, so memory is roughly `2 * set_bits` bytes while the fill is
+/// below 1/16. Past that chunks switch to 8KiB bitmaps and the filter is
+/// no smaller than the dense m/8 bytes, convert it with `densify`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseBloomFilter {
+    /// number of bits in a Bloom filter
+    m: u64,
+    /// number of hash functions
+    k: u64,
+
+    storage: RoaringTreemap,
+}
+
+impl SparseBloomFilter {
+    /// approximate number of bytes used by the set bit positions
+    pub fn memory_bytes(&self) -> usize {
+        self.storage.serialized_size()
+    }
+
+    /// converts into a dense filter with identical bits
+    pub fn densify(self) -> ClassicalBloomFilter {
+        let mut storage = zeroed(self.m as usize);
+        for idx in &self.storage {
+            storage.set(idx as usize, true);
+        }
+        ClassicalBloomFilter::from_parts(self.m, self.k, storage)
+            .expect("storage is built with m bits")
+    }
+}
+
+impl DoubleHashing for SparseBloomFilter {}
+
+impl Filter for SparseBloomFilter {
+    /// n -- number of elements to insert
+    /// f -- the false positive rate
+    fn new(n: u32, f: f64) -> Self {
+        let m = Self::calculate_m(f, n);
+        Self {
+            m,
+            k: Self::calculate_k(m, n),
+            storage: RoaringTreemap::new(),
+        }
+    }
+
+    fn insert(&mut self, value: &[u8]) {
+        for idx in Self::probes(value, self.k, self.m) {
+            self.storage.insert(idx as u64);
+        }
+    }
+
+    fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize> {
+        let mut indices: Vec<usize> = Self::probes(value, self.k, self.m).collect();
+        for &idx in &indices {
+            self.storage.insert(idx as u64);
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    fn lookup(&self, value: &[u8]) -> bool {
+        Self::probes(value, self.k, self.m).all(|idx| self.storage.contains(idx as u64))
+    }
+
+    fn get_size(&self) -> usize {
+        self.m as usize
+    }
+
+    fn num_bits(&self) -> u64 {
+        self.m
+    }
+
+    fn num_hashes(&self) -> u64 {
+        self.k
+    }
+
+    fn set_bits(&self) -> u64 {
+        self.storage.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparse_simple_check() {
+        let mut bf = SparseBloomFilter::new(10, 0.01);
+        bf.insert(&1u32.to_be_bytes());
+        bf.insert(&10u32.to_be_bytes());
+        bf.insert(&30u32.to_be_bytes());
+
+        assert!(bf.lookup(&1u32.to_be_bytes()), "stored value is not found!");
+        assert!(
+            bf.lookup(&10u32.to_be_bytes()),
+            "stored value is not found!"
+        );
+        assert!(
+            bf.lookup(&30u32.to_be_bytes()),
+            "stored value is not found!"
+        );
+        assert!(
+            !bf.lookup(&45u32.to_be_bytes()),
+            "not stored value is found!"
+        );
+    }
+
+    #[test]
+    fn sparse_matches_dense() {
+        let mut sparse = SparseBloomFilter::new(10u32.pow(6), 0.01);
+        let mut dense = ClassicalBloomFilter::new(10u32.pow(6), 0.01);
+        for i in 0..1000u32 {
+            sparse.insert(&i.to_be_bytes());
+            dense.insert(&i.to_be_bytes());
+        }
+
+        assert!(sparse.memory_bytes() * 50 < dense.get_size() / 8);
+        for i in 0..20000u32 {
+            assert_eq!(
+                sparse.lookup(&i.to_be_bytes()),
+                dense.lookup(&i.to_be_bytes())
+            );
+        }
+        assert_eq!(sparse.densify(), dense);
+    }
+}
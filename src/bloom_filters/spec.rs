@@ -0,0 +1,130 @@
+#[cfg(feature = "roaring")]
+use crate::bloom_filters::SparseBloomFilter;
+use crate::bloom_filters::{
+    ClassicalBloomFilter, Filter, FilterDyn, FilterError, PartitionedBloomFilter,
+};
+
+/// Builds a filter from a spec like `classical:n=100000,f=0.01`.
+/// Known types are classical, partitioned and sparse (with the `roaring`
+/// feature), all need n and f,
+/// partitioned also takes an optional number of partitions:
+/// `partitioned:n=1000,f=0.01,partitions=8`.
+pub fn from_spec(spec: &str) -> Result<Box<dyn FilterDyn>, FilterError> {
+    let (name, params) = spec.split_once(':').unwrap_or((spec, ""));
+    let mut n = None;
+    let mut f = None;
+    let mut partitions = None;
+    for param in params.split(',').filter(|param| !param.is_empty()) {
+        let (key, value) = param.split_once('=').ok_or_else(|| {
+            FilterError::InvalidSpec(format!("expected key=value, got {param:?}"))
+        })?;
+        match key.trim() {
+            "n" => n = Some(parse(key, value)?),
+            "f" => f = Some(parse(key, value)?),
+            "partitions" => partitions = Some(parse(key, value)?),
+            _ => {
+                return Err(FilterError::InvalidSpec(format!(
+                    "unknown parameter {key:?}"
+                )))
+            }
+        }
+    }
+
+    let n: u32 = n.ok_or_else(|| FilterError::InvalidSpec("missing n".into()))?;
+    let f: f64 = f.ok_or_else(|| FilterError::InvalidSpec("missing f".into()))?;
+    if n == 0 {
+        return Err(FilterError::InvalidSpec("n must be >= 1".into()));
+    }
+    if !(f > 0.0 && f < 1.0) {
+        return Err(FilterError::InvalidSpec(format!(
+            "f must be in (0, 1), got {f}"
+        )));
+    }
+    if ClassicalBloomFilter::calculate_m(f, n) == 0 {
+        return Err(FilterError::InvalidSpec(format!(
+            "n={n} and f={f} round to a filter of 0 bits"
+        )));
+    }
+    if partitions.is_some() && name.trim() != "partitioned" {
+        return Err(FilterError::InvalidSpec(format!(
+            "partitions isn't a parameter of {name:?}"
+        )));
+    }
+
+    match name.trim() {
+        "classical" => Ok(Box::new(ClassicalBloomFilter::new(n, f))),
+        "partitioned" => match partitions {
+            Some(0) => Err(FilterError::InvalidSpec("partitions must be >= 1".into())),
+            Some(p) => Ok(Box::new(PartitionedBloomFilter::with_partitions(n, f, p))),
+            None => Ok(Box::new(PartitionedBloomFilter::new(n, f))),
+        },
+        #[cfg(feature = "roaring")]
+        "sparse" => Ok(Box::new(SparseBloomFilter::new(n, f))),
+        other => Err(FilterError::UnknownFilterType(other.to_string())),
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, FilterError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| FilterError::InvalidSpec(format!("can't parse {key}={value:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_specs() {
+        let mut bf = from_spec("classical:n=1000,f=0.01").unwrap();
+        assert_eq!(bf.num_bits(), ClassicalBloomFilter::calculate_m(0.01, 1000));
+        bf.insert(&1u32.to_be_bytes());
+        assert!(bf.lookup(&1u32.to_be_bytes()), "stored value is not found!");
+
+        let bf = from_spec("partitioned:n=1000,f=0.01,partitions=4").unwrap();
+        assert_eq!(bf.get_size(), 4);
+        let mut bf = from_spec("partitioned:n=1000,f=0.01,partitions=32").unwrap();
+        for i in 0..1000u32 {
+            bf.insert(&i.to_be_bytes());
+        }
+        let false_positives = (1000..101000u32)
+            .filter(|i| bf.lookup(&i.to_be_bytes()))
+            .count();
+        assert!(false_positives < 1500, "{false_positives} false positives");
+        let bf = from_spec("partitioned: n=1000, f=0.01").unwrap();
+        assert_eq!(bf.get_size(), bf.num_hashes() as usize);
+        #[cfg(feature = "roaring")]
+        assert!(from_spec("sparse:f=0.01,n=1000").is_ok());
+    }
+
+    #[test]
+    fn unknown_type() {
+        let res = from_spec("cuckoo:n=1000,f=0.01");
+        assert_eq!(
+            res.err(),
+            Some(FilterError::UnknownFilterType("cuckoo".into()))
+        );
+    }
+
+    #[test]
+    fn malformed_params() {
+        for spec in [
+            "classical",
+            "classical:n=1000",
+            "classical:n=1000,f",
+            "classical:n=abc,f=0.01",
+            "classical:n=1000,f=1.5",
+            "classical:n=1000,f=0.01,m=5",
+            "classical:n=1000,f=0.01,partitions=2",
+            "partitioned:n=1000,f=0.01,partitions=0",
+            "classical:n=0,f=0.01",
+            "classical:n=1,f=0.9",
+        ] {
+            assert!(
+                matches!(from_spec(spec), Err(FilterError::InvalidSpec(_))),
+                "{spec} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::bloom_filters::Filter;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// number of histogram buckets, bucket b holds durations in [2^(b-1), 2^b) ns
+const BUCKETS: usize = 65;
+
+/// Wraps a filter to record how long every insert and lookup takes in a
+/// log2 histogram of nanoseconds. Unwrapped filters pay nothing for it.
+pub struct TimedFilter<F> {
+    filter: F,
+    histogram: [Cell<u64>; BUCKETS],
+}
+
+impl<F: Filter> TimedFilter<F> {
+    pub fn wrap(filter: F) -> Self {
+        Self {
+            filter,
+            histogram: std::array::from_fn(|_| Cell::new(0)),
+        }
+    }
+
+    fn record(&self, elapsed: Duration) {
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        let bucket = &self.histogram[(u64::BITS - nanos.leading_zeros()) as usize];
+        bucket.set(bucket.get() + 1);
+    }
+
+    /// number of timed operations
+    pub fn count(&self) -> u64 {
+        self.histogram.iter().map(Cell::get).sum()
+    }
+
+    /// Upper bound of the histogram bucket holding the q-th quantile of
+    /// the recorded durations, q in [0, 1]. Zero before anything was timed.
+    pub fn percentile(&self, q: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, hits) in self.histogram.iter().enumerate() {
+            seen += hits.get();
+            if seen >= rank {
+                return Duration::from_nanos(1u64.checked_shl(bucket as u32).unwrap_or(u64::MAX));
+            }
+        }
+        Duration::from_nanos(u64::MAX)
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.percentile(0.5)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.percentile(0.99)
+    }
+
+    /// forgets all recorded timings
+    pub fn reset(&mut self) {
+        self.histogram.iter().for_each(|bucket| bucket.set(0));
+    }
+
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    pub fn into_inner(self) -> F {
+        self.filter
+    }
+}
+
+impl<F: Filter> Filter for TimedFilter<F> {
+    fn new(n: u32, f: f64) -> Self {
+        Self::wrap(F::new(n, f))
+    }
+
+    fn insert(&mut self, value: &[u8]) {
+        let start = Instant::now();
+        self.filter.insert(value);
+        self.record(start.elapsed());
+    }
+
+    fn insert_tracked(&mut self, value: &[u8]) -> Vec<usize> {
+        let start = Instant::now();
+        let indices = self.filter.insert_tracked(value);
+        self.record(start.elapsed());
+        indices
+    }
+
+    fn lookup(&self, value: &[u8]) -> bool {
+        let start = Instant::now();
+        let found = self.filter.lookup(value);
+        self.record(start.elapsed());
+        found
+    }
+
+    fn get_size(&self) -> usize {
+        self.filter.get_size()
+    }
+
+    fn num_bits(&self) -> u64 {
+        self.filter.num_bits()
+    }
+
+    fn num_hashes(&self) -> u64 {
+        self.filter.num_hashes()
+    }
+
+    fn set_bits(&self) -> u64 {
+        self.filter.set_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom_filters::{ClassicalBloomFilter, FilterDyn};
+
+    #[test]
+    fn timed_filter_percentiles() {
+        let mut timed = TimedFilter::<ClassicalBloomFilter>::new(10000, 0.01);
+        assert_eq!(timed.count(), 0);
+        assert_eq!(timed.p50(), Duration::ZERO);
+
+        for i in 0..10000u32 {
+            timed.insert(&i.to_be_bytes());
+        }
+        let filter: &dyn FilterDyn = &timed;
+        for i in 0..10000u32 {
+            assert!(
+                filter.lookup(&i.to_be_bytes()),
+                "stored value is not found!"
+            );
+        }
+
+        assert_eq!(timed.count(), 20000);
+        assert!(timed.p50() > Duration::ZERO);
+        assert!(timed.p50() <= timed.p99());
+        assert!(timed.p99() < Duration::from_millis(10));
+
+        timed.reset();
+        assert_eq!(timed.count(), 0);
+    }
+}